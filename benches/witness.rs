@@ -0,0 +1,88 @@
+use circom_scotia::r1cs::CircomInput;
+use circom_scotia::witness::WitnessCalculator;
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use ff::Field;
+use pasta_curves::vesta::Base as Fr;
+use std::env::current_dir;
+
+// Benchmarks the witness calculator alone, so that neither an r1cs file nor the calculator pool of
+// `CircomConfig` is involved.
+
+fn setup(circuit: &str, wasm: &str) -> WitnessCalculator {
+    let path = current_dir()
+        .unwrap()
+        .join("circom")
+        .join(circuit)
+        .join(wasm);
+    WitnessCalculator::new(path).unwrap()
+}
+
+fn sha256_benchmark(c: &mut Criterion) {
+    let mut group = c.benchmark_group("witness_calculator");
+    group.bench_function("sha256", |b| {
+        let mut wtns = setup("sha256", "circom_sha256.wasm");
+        let input = vec![CircomInput::new("arg_in".into(), vec![Fr::ZERO, Fr::ZERO])];
+        b.iter_batched(
+            || input.clone(),
+            |input| {
+                wtns.calculate_witness(black_box(input), true)
+                    .expect("Failed to calculate witness");
+            },
+            criterion::BatchSize::SmallInput,
+        )
+    });
+}
+
+fn keccak_benchmark(c: &mut Criterion) {
+    let mut group = c.benchmark_group("witness_calculator");
+    group.sample_size(20);
+    group.bench_function("keccak", |b| {
+        let mut wtns = setup("keccak", "circom_keccak256.wasm");
+        let input = vec![CircomInput::new(
+            "in".into(),
+            (0..256).map(|i| Fr::from(i % 3 == 0)).collect(),
+        )];
+        b.iter_batched(
+            || input.clone(),
+            |input| {
+                wtns.calculate_witness(black_box(input), true)
+                    .expect("Failed to calculate witness");
+            },
+            criterion::BatchSize::SmallInput,
+        )
+    });
+}
+
+// Compares reading and writing the limbs of field elements directly in the instance memory with
+// going through `readSharedRWMemory` and `writeSharedRWMemory`, one limb at a time.
+#[cfg(feature = "wasmer")]
+fn shared_rw_memory_benchmark(c: &mut Criterion) {
+    let mut group = c.benchmark_group("shared_rw_memory");
+    for direct in [true, false] {
+        let name = if direct { "direct" } else { "per_limb" };
+        group.bench_function(name, |b| {
+            let mut wtns = setup("sha256", "circom_sha256.wasm");
+            wtns.set_direct_memory_access(direct).unwrap();
+            let input = vec![CircomInput::new("arg_in".into(), vec![Fr::ZERO, Fr::ZERO])];
+            b.iter_batched(
+                || input.clone(),
+                |input| {
+                    wtns.calculate_witness(black_box(input), true)
+                        .expect("Failed to calculate witness");
+                },
+                criterion::BatchSize::SmallInput,
+            )
+        });
+    }
+}
+
+#[cfg(feature = "wasmer")]
+criterion_group!(
+    benches,
+    sha256_benchmark,
+    keccak_benchmark,
+    shared_rw_memory_benchmark
+);
+#[cfg(not(feature = "wasmer"))]
+criterion_group!(benches, sha256_benchmark, keccak_benchmark);
+criterion_main!(benches);
@@ -0,0 +1,132 @@
+;; Hand-written stand-in for a circom 2 witness generator compiled with `--prime goldilocks`.
+;;
+;; It follows the export and import interface of circom 2.1 wasm witness generators for the
+;; circuit below, with field elements stored as two 32-bit limbs (`getFieldNumLen32() == 2`).
+;;
+;;   template Adder() {
+;;       signal input a;
+;;       signal input b;
+;;       signal output c;
+;;       assert(a != b);
+;;       c <== a + b;
+;;       log("a + b =", c);
+;;   }
+;;   component main = Adder();
+;;
+;; The witness is laid out as [1, c, a, b].
+;;
+;; This is not the output of the circom compiler, which could not be run where this fixture was
+;; written. Real builds for BN254 are exercised by circom/multiplier_v1 and circom/multiplier_v2, but
+;; the two-limb field elements of small primes are only exercised here, so that a real build with
+;; `--prime goldilocks` should replace this file.
+(module
+  (import "runtime" "exceptionHandler" (func $exceptionHandler (param i32)))
+  (import "runtime" "printErrorMessage" (func $printErrorMessage))
+  (import "runtime" "writeBufferMessage" (func $writeBufferMessage))
+  (import "runtime" "showSharedRWMemory" (func $showSharedRWMemory))
+
+  (memory (export "memory") 1)
+
+  ;; Shared read-write memory starts at 64, the witness signals at 128.
+  (global $shared i32 (i32.const 64))
+  (global $signals i32 (i32.const 128))
+  (global $prime i64 (i64.const 0xFFFFFFFF00000001))
+  ;; Bit mask of the inputs that have been set.
+  (global $set (mut i32) (i32.const 0))
+
+  ;; Null terminated messages, read one character at a time through `getMessageChar`.
+  (data (i32.const 512) "a + b =\00")
+  (data (i32.const 528) "\n\00")
+  (data (i32.const 544) "Error in template Adder_0 line: 5\00")
+  (global $message (mut i32) (i32.const 0))
+
+  (func (export "getVersion") (result i32) (i32.const 2))
+  (func (export "getMinorVersion") (result i32) (i32.const 1))
+  (func (export "getPatchVersion") (result i32) (i32.const 6))
+  (func (export "getSharedRWMemoryStart") (result i32) (global.get $shared))
+  (func (export "getFieldNumLen32") (result i32) (i32.const 2))
+  (func (export "getWitnessSize") (result i32) (i32.const 4))
+  (func (export "getInputSize") (result i32) (i32.const 2))
+
+  (func (export "getMessageChar") (result i32)
+    (local $c i32)
+    (local.set $c (i32.load8_u (global.get $message)))
+    (if (local.get $c)
+      (then (global.set $message (i32.add (global.get $message) (i32.const 1)))))
+    (local.get $c))
+
+  (func (export "readSharedRWMemory") (param $i i32) (result i32)
+    (i32.load (i32.add (global.get $shared) (i32.shl (local.get $i) (i32.const 2)))))
+
+  (func (export "writeSharedRWMemory") (param $i i32) (param $v i32)
+    (i32.store (i32.add (global.get $shared) (i32.shl (local.get $i) (i32.const 2))) (local.get $v)))
+
+  (func (export "getRawPrime")
+    (i64.store (global.get $shared) (global.get $prime)))
+
+  (func (export "init") (param $sanity_check i32)
+    (memory.fill (global.get $signals) (i32.const 0) (i32.const 32))
+    (i64.store (global.get $signals) (i64.const 1))
+    (global.set $set (i32.const 0)))
+
+  ;; Returns the witness index of the input signal with the given name hash, or 0 if there is none.
+  (func $inputIndex (param $hmsb i32) (param $hlsb i32) (result i32)
+    ;; fnv("a")
+    (if (i32.and (i32.eq (local.get $hmsb) (i32.const 0xaf63dc4c))
+                 (i32.eq (local.get $hlsb) (i32.const 0x8601ec8c)))
+      (then (return (i32.const 2))))
+    ;; fnv("b")
+    (if (i32.and (i32.eq (local.get $hmsb) (i32.const 0xaf63df4c))
+                 (i32.eq (local.get $hlsb) (i32.const 0x8601f1a5)))
+      (then (return (i32.const 3))))
+    (i32.const 0))
+
+  (func (export "getInputSignalSize") (param $hmsb i32) (param $hlsb i32) (result i32)
+    (i32.ne (call $inputIndex (local.get $hmsb) (local.get $hlsb)) (i32.const 0)))
+
+  (func (export "setInputSignal") (param $hmsb i32) (param $hlsb i32) (param $pos i32)
+    (local $idx i32)
+    (local.set $idx (call $inputIndex (local.get $hmsb) (local.get $hlsb)))
+    (if (i32.eqz (local.get $idx))
+      (then (call $exceptionHandler (i32.const 1)) (unreachable)))
+    (if (i32.ne (local.get $pos) (i32.const 0))
+      (then (call $exceptionHandler (i32.const 2)) (unreachable)))
+    (if (i32.and (global.get $set) (i32.shl (i32.const 1) (local.get $idx)))
+      (then (call $exceptionHandler (i32.const 3)) (unreachable)))
+    (global.set $set (i32.or (global.get $set) (i32.shl (i32.const 1) (local.get $idx))))
+    (i64.store
+      (i32.add (global.get $signals) (i32.shl (local.get $idx) (i32.const 3)))
+      (i64.load (global.get $shared)))
+    (if (i32.eq (global.get $set) (i32.const 12))
+      (then (call $run))))
+
+  ;; assert(a != b), then c <== a + b, where 2^64 = 2^32 - 1 modulo the prime, then logs c.
+  (func $run
+    (local $a i64)
+    (local $c i64)
+    (local.set $a (i64.load offset=16 (global.get $signals)))
+    (if (i64.eq (local.get $a) (i64.load offset=24 (global.get $signals)))
+      (then
+        (global.set $message (i32.const 544))
+        (call $printErrorMessage)
+        (call $exceptionHandler (i32.const 4))
+        (unreachable)))
+    (local.set $c (i64.add (local.get $a) (i64.load offset=24 (global.get $signals))))
+    (if (i64.lt_u (local.get $c) (local.get $a))
+      (then (local.set $c (i64.add (local.get $c) (i64.const 0xFFFFFFFF))))
+      (else
+        (if (i64.ge_u (local.get $c) (global.get $prime))
+          (then (local.set $c (i64.sub (local.get $c) (global.get $prime)))))))
+    (i64.store offset=8 (global.get $signals) (local.get $c))
+    (global.set $message (i32.const 512))
+    (call $writeBufferMessage)
+    (i64.store (global.get $shared) (local.get $c))
+    (call $showSharedRWMemory)
+    (global.set $message (i32.const 528))
+    (call $writeBufferMessage))
+
+  (func (export "getWitness") (param $i i32)
+    (i64.store
+      (global.get $shared)
+      (i64.load (i32.add (global.get $signals) (i32.shl (local.get $i) (i32.const 3))))))
+)
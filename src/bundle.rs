@@ -0,0 +1,757 @@
+// Copyright (c) Lurk Lab
+// SPDX-License-Identifier: MIT
+//! # Bug-report bundles
+//!
+//! This module captures failed witness calculations and syntheses as self-contained bundles, which can be sent along
+//! with a bug report and replayed elsewhere to reproduce the failure.
+//!
+//! A bundle is a directory, or a tar archive of that directory, holding:
+//! - `manifest.json`: the [`Manifest`] of the run, with the versions of this crate and of wasmer, the hashes of the
+//!   circuit files, the shape of the inputs and the chain of errors the run failed with.
+//! - `circuit.wasm`, and `circuit.r1cs` for syntheses: the files of the circuit.
+//! - `inputs.json`: the inputs of the run, unless they were redacted.
+//! - `witness.wtns`: the witness a synthesis was run with, unless the inputs were redacted since the witness holds them.
+//! - `logs.txt`: the messages the circuit printed before failing, if any.
+//!
+//! The messages of the circuit, and the witness of a synthesis, are partial outputs of the run. Those not recorded
+//! with [`BugReport::with_logs`] and [`BugReport::with_witness`] are collected by calculating the witness again when
+//! the bundle is written.
+//!
+//! Bundles are captured with [`BugReport`], and replayed with [`Bundle::replay`]. This module is available with the
+//! `bundle` feature.
+use std::borrow::Cow;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, BufReader};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use bellpepper_core::test_cs::TestConstraintSystem;
+use ff::{PrimeField, PrimeFieldBits};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::error::BundleError::{self, CorruptedFile, LoadError, NonMatchingField, RedactedInputs};
+use crate::r1cs::CircomInput;
+use crate::reader::{
+    inputs_as_json, load_inputs_from_json, load_r1cs, load_witness_from_bin_file,
+    write_witness_to_bin_writer,
+};
+use crate::synthesize;
+use crate::witness::{LogSink, WitnessCalculator, WitnessCalculatorError};
+
+const MANIFEST: &str = "manifest.json";
+const WASM: &str = "circuit.wasm";
+const R1CS: &str = "circuit.r1cs";
+const INPUTS: &str = "inputs.json";
+const WITNESS: &str = "witness.wtns";
+const LOGS: &str = "logs.txt";
+
+/// The name and contents of a file of a bundle.
+type BundleFile = (&'static str, Vec<u8>);
+
+/// Number of tar bundles opened by this process, which tells their extraction directories apart.
+static EXTRACTIONS: AtomicUsize = AtomicUsize::new(0);
+
+/// The kind of run a bundle was captured for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RunKind {
+    /// A witness calculation.
+    Witness,
+    /// A synthesis of the constraints of a circuit with its witness.
+    Synthesis,
+}
+
+/// The name and number of values of an input signal.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InputShape {
+    pub name: String,
+    pub len: usize,
+}
+
+/// Description of a failed run, written to the `manifest.json` file of its bundle.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Manifest {
+    pub kind: RunKind,
+    /// The version of this crate.
+    pub crate_version: String,
+    /// The version of wasmer, if the crate was built with the `wasmer` feature.
+    pub wasmer_version: Option<String>,
+    /// The modulus of the field of the run.
+    pub field: String,
+    /// The SHA-256 hash of the WebAssembly module of the circuit, in hexadecimal.
+    pub wasm_sha256: String,
+    /// The SHA-256 hash of the r1cs file of the circuit, in hexadecimal, for syntheses.
+    pub r1cs_sha256: Option<String>,
+    pub sanity_check: bool,
+    pub inputs: Vec<InputShape>,
+    /// Whether the values of the inputs, and the witness holding them, were left out of the bundle.
+    pub redacted: bool,
+    /// The messages of the error the run failed with, followed by the messages of its sources.
+    pub error: Vec<String>,
+}
+
+/// A failed run, to be captured as a bundle.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use std::path::Path;
+/// # use circom_scotia::{bundle::BugReport, r1cs::CircomInput, witness::WitnessCalculator};
+/// # use ff::Field;
+/// # use pasta_curves::vesta::Base as Fr;
+/// let wasm = Path::new("circuit.wasm");
+/// let inputs = vec![CircomInput::new("in".into(), vec![Fr::ZERO])];
+/// let mut wtns = WitnessCalculator::new(wasm).unwrap();
+/// if let Err(err) = wtns.calculate_witness(inputs.clone(), true) {
+///     BugReport::witness(wasm, &inputs, true)
+///         .redact_inputs()
+///         .write_tar("bug-report.tar", &err)
+///         .unwrap();
+/// }
+/// ```
+#[derive(Debug)]
+pub struct BugReport<'a, F: PrimeField> {
+    kind: RunKind,
+    wasm: &'a Path,
+    r1cs: Option<&'a Path>,
+    inputs: &'a [CircomInput<F>],
+    sanity_check: bool,
+    redact_inputs: bool,
+    witness: Option<&'a [F]>,
+    logs: Option<&'a [String]>,
+}
+
+impl<'a, F: PrimeFieldBits> BugReport<'a, F> {
+    /// Describes a failed witness calculation of the circuit compiled to `wasm`.
+    pub fn witness(wasm: &'a Path, inputs: &'a [CircomInput<F>], sanity_check: bool) -> Self {
+        Self {
+            kind: RunKind::Witness,
+            wasm,
+            r1cs: None,
+            inputs,
+            sanity_check,
+            redact_inputs: false,
+            witness: None,
+            logs: None,
+        }
+    }
+
+    /// Describes a failed synthesis of the circuit compiled to `wasm` and `r1cs`, with the witness calculated for
+    /// `inputs`.
+    pub fn synthesis(
+        wasm: &'a Path,
+        r1cs: &'a Path,
+        inputs: &'a [CircomInput<F>],
+        sanity_check: bool,
+    ) -> Self {
+        Self {
+            kind: RunKind::Synthesis,
+            r1cs: Some(r1cs),
+            ..Self::witness(wasm, inputs, sanity_check)
+        }
+    }
+
+    /// Leaves the values of the inputs, and the witness holding them, out of the bundle. Only the names and lengths of
+    /// the inputs are recorded, so that the inputs must be provided again to replay the run.
+    pub fn redact_inputs(mut self) -> Self {
+        self.redact_inputs = true;
+        self
+    }
+
+    /// Records the witness the synthesis was run with, so that it is replayed with the same witness. Otherwise, the
+    /// witness is calculated again when the bundle is written.
+    pub fn with_witness(mut self, witness: &'a [F]) -> Self {
+        self.witness = Some(witness);
+        self
+    }
+
+    /// Records the messages the circuit printed before failing, as collected with [`LogSink::Collect`]. Otherwise, they
+    /// are collected by calculating the witness again when the bundle is written.
+    pub fn with_logs(mut self, logs: &'a [String]) -> Self {
+        self.logs = Some(logs);
+        self
+    }
+
+    /// Writes the bundle of the run, which failed with `error`, to the directory `dir`.
+    ///
+    /// The files of a bundle written to `dir` before are replaced, and those this bundle does not hold, such as the
+    /// inputs of a redacted bundle, are removed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the files of the circuit cannot be read, or if the bundle cannot be written.
+    pub fn write_dir(
+        &self,
+        dir: impl AsRef<Path>,
+        error: &(dyn Error + 'static),
+    ) -> Result<Manifest, BundleError> {
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir)?;
+        let (manifest, files) = self.files(error)?;
+        for name in [R1CS, INPUTS, WITNESS, LOGS] {
+            match fs::remove_file(dir.join(name)) {
+                Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err.into()),
+                _ => {}
+            }
+        }
+        for (name, bytes) in files {
+            fs::write(dir.join(name), bytes)?;
+        }
+        Ok(manifest)
+    }
+
+    /// Writes the bundle of the run, which failed with `error`, to the tar archive `path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the files of the circuit cannot be read, or if the bundle cannot be written.
+    pub fn write_tar(
+        &self,
+        path: impl AsRef<Path>,
+        error: &(dyn Error + 'static),
+    ) -> Result<Manifest, BundleError> {
+        let (manifest, files) = self.files(error)?;
+        let mut archive = tar::Builder::new(File::create(path)?);
+        for (name, bytes) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(bytes.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            archive.append_data(&mut header, name, bytes.as_slice())?;
+        }
+        archive.into_inner()?.sync_all()?;
+        Ok(manifest)
+    }
+
+    /// Returns the manifest of the bundle, and the names and contents of its files.
+    fn files(
+        &self,
+        error: &(dyn Error + 'static),
+    ) -> Result<(Manifest, Vec<BundleFile>), BundleError> {
+        let mut files = vec![];
+
+        let wasm = fs::read(self.wasm)?;
+        let wasm_sha256 = sha256_hex(&wasm);
+        files.push((WASM, wasm));
+
+        let r1cs_sha256 = match self.r1cs {
+            Some(r1cs) => {
+                let r1cs = fs::read(r1cs)?;
+                let hash = sha256_hex(&r1cs);
+                files.push((R1CS, r1cs));
+                Some(hash)
+            }
+            None => None,
+        };
+
+        let mut witness = self.witness.map(Cow::Borrowed);
+        let mut logs = self.logs.map(Cow::Borrowed);
+        let synthesis = self.kind == RunKind::Synthesis;
+        if logs.is_none() || (synthesis && witness.is_none()) {
+            let (calculated, collected) =
+                calculate_witness(self.wasm, self.inputs.to_vec(), self.sanity_check);
+            logs.get_or_insert(Cow::Owned(collected));
+            if synthesis && witness.is_none() {
+                witness = calculated.ok().map(Cow::Owned);
+            }
+        }
+
+        if !self.redact_inputs {
+            files.push((INPUTS, inputs_as_json(self.inputs).into_bytes()));
+            if let Some(witness) = witness {
+                let mut bytes = vec![];
+                write_witness_to_bin_writer(&mut bytes, &witness)?;
+                files.push((WITNESS, bytes));
+            }
+        }
+
+        if let Some(logs) = logs.filter(|logs| !logs.is_empty()) {
+            files.push((LOGS, logs.join("\n").into_bytes()));
+        }
+
+        let manifest = Manifest {
+            kind: self.kind,
+            crate_version: env!("CARGO_PKG_VERSION").to_string(),
+            wasmer_version: wasmer_version(),
+            field: F::MODULUS.to_string(),
+            wasm_sha256,
+            r1cs_sha256,
+            sanity_check: self.sanity_check,
+            inputs: self
+                .inputs
+                .iter()
+                .map(|input| InputShape {
+                    name: input.name.clone(),
+                    len: input.value.len(),
+                })
+                .collect(),
+            redacted: self.redact_inputs,
+            error: error_chain(error),
+        };
+        files.insert(0, (MANIFEST, serde_json::to_vec_pretty(&manifest)?));
+
+        Ok((manifest, files))
+    }
+}
+
+/// A bundle opened to be replayed.
+///
+/// Bundles archived with tar are extracted to a temporary directory, which is removed when the [`Bundle`] is dropped.
+#[derive(Debug)]
+pub struct Bundle {
+    pub manifest: Manifest,
+    dir: PathBuf,
+    extracted: bool,
+}
+
+/// The outcome of replaying a bundle.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Replay<F: PrimeField> {
+    /// The messages of the error the replayed run failed with, followed by the messages of its sources, or `None` if it
+    /// succeeded.
+    pub error: Option<Vec<String>>,
+    /// The witness calculated or synthesized by the replayed run, if it got that far.
+    pub witness: Option<Vec<F>>,
+    /// The messages the circuit printed during the replayed witness calculation.
+    pub logs: Vec<String>,
+    /// The messages of the error the captured run failed with, see [`Manifest::error`].
+    pub captured_error: Vec<String>,
+}
+
+impl<F: PrimeField> Replay<F> {
+    /// Returns whether the replayed run failed with the error of the captured run.
+    ///
+    /// The messages of the replayed error and its sources must end the captured ones, which may wrap the error in
+    /// errors of the caller.
+    pub fn reproduced(&self) -> bool {
+        self.error
+            .as_ref()
+            .is_some_and(|error| self.captured_error.ends_with(error))
+    }
+}
+
+impl Bundle {
+    /// Opens the bundle at `path`, either a directory or a tar archive.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the bundle cannot be read, if its manifest is invalid, or if its files do not match the
+    /// hashes recorded in its manifest.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, BundleError> {
+        let path = path.as_ref();
+        let (dir, extracted) = if path.is_dir() {
+            (path.to_path_buf(), false)
+        } else {
+            let dir = std::env::temp_dir().join(format!(
+                "circom-scotia-bundle-{}-{}",
+                std::process::id(),
+                EXTRACTIONS.fetch_add(1, Ordering::Relaxed)
+            ));
+            tar::Archive::new(File::open(path)?).unpack(&dir)?;
+            (dir, true)
+        };
+
+        match Self::load_manifest(&dir) {
+            Ok(manifest) => Ok(Self {
+                manifest,
+                dir,
+                extracted,
+            }),
+            Err(err) => {
+                if extracted {
+                    let _ = fs::remove_dir_all(&dir);
+                }
+                Err(err)
+            }
+        }
+    }
+
+    /// Reads the manifest of the bundle extracted to `dir`, and checks the bundled files against it.
+    fn load_manifest(dir: &Path) -> Result<Manifest, BundleError> {
+        let manifest: Manifest =
+            serde_json::from_reader(BufReader::new(File::open(dir.join(MANIFEST))?))?;
+
+        check_file(dir, WASM, Some(&manifest.wasm_sha256))?;
+        check_file(dir, R1CS, manifest.r1cs_sha256.as_ref())?;
+        if manifest.crate_version != env!("CARGO_PKG_VERSION")
+            || manifest.wasmer_version != wasmer_version()
+        {
+            warn!(
+                "replaying a bundle captured with circom-scotia {} and wasmer {}",
+                manifest.crate_version,
+                manifest.wasmer_version.as_deref().unwrap_or("disabled")
+            );
+        }
+
+        Ok(manifest)
+    }
+
+    /// Returns the bundled inputs, or `None` if they were redacted.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the inputs cannot be read as elements of the field `F`.
+    pub fn inputs<F: PrimeField>(&self) -> Result<Option<Vec<CircomInput<F>>>, BundleError> {
+        if self.manifest.redacted {
+            return Ok(None);
+        }
+
+        let file = File::open(self.dir.join(INPUTS))?;
+        load_inputs_from_json(BufReader::new(file))
+            .map(Some)
+            .map_err(|err| LoadError {
+                file: INPUTS.to_string(),
+                source: err.into(),
+            })
+    }
+
+    /// Replays the captured run with the bundled inputs.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the inputs were redacted, see [`Bundle::replay_with_inputs`].
+    pub fn replay<F: PrimeFieldBits>(&self) -> Result<Replay<F>, BundleError> {
+        let inputs = self.inputs()?.ok_or(RedactedInputs)?;
+        self.run(inputs, true)
+    }
+
+    /// Replays the captured run with the given inputs, such as the inputs of a redacted bundle.
+    ///
+    /// Syntheses are replayed with a witness calculated for these inputs rather than the bundled witness.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the bundle was captured for a field other than `F`, or if its files cannot be loaded. The
+    /// failure of the replayed run is reported in the returned [`Replay`].
+    pub fn replay_with_inputs<F: PrimeFieldBits>(
+        &self,
+        inputs: Vec<CircomInput<F>>,
+    ) -> Result<Replay<F>, BundleError> {
+        self.run(inputs, false)
+    }
+
+    fn run<F: PrimeFieldBits>(
+        &self,
+        inputs: Vec<CircomInput<F>>,
+        bundled_witness: bool,
+    ) -> Result<Replay<F>, BundleError> {
+        if self.manifest.field != F::MODULUS {
+            return Err(NonMatchingField {
+                expected: self.manifest.field.clone(),
+                actual: F::MODULUS.to_string(),
+            });
+        }
+
+        let witness_file = self.dir.join(WITNESS);
+        let (witness, logs) = if bundled_witness && witness_file.exists() {
+            let witness = load_witness_from_bin_file(&witness_file).map_err(|err| LoadError {
+                file: WITNESS.to_string(),
+                source: err.into(),
+            })?;
+            (Ok(witness), vec![])
+        } else {
+            calculate_witness(&self.dir.join(WASM), inputs, self.manifest.sanity_check)
+        };
+
+        let witness = match (witness, self.manifest.kind) {
+            (Err(err), _) => {
+                return Ok(Replay {
+                    error: Some(error_chain(&err)),
+                    witness: None,
+                    logs,
+                    captured_error: self.manifest.error.clone(),
+                })
+            }
+            (Ok(witness), RunKind::Witness) => {
+                return Ok(Replay {
+                    error: None,
+                    witness: Some(witness),
+                    logs,
+                    captured_error: self.manifest.error.clone(),
+                })
+            }
+            (Ok(witness), RunKind::Synthesis) => witness,
+        };
+
+        let r1cs = load_r1cs(self.dir.join(R1CS)).map_err(|err| LoadError {
+            file: R1CS.to_string(),
+            source: err.into(),
+        })?;
+        let mut cs = TestConstraintSystem::<F>::new();
+        let error = match synthesize(&mut cs, r1cs, Some(witness.clone())) {
+            Err(err) => Some(error_chain(&err)),
+            Ok(_) => cs
+                .which_is_unsatisfied()
+                .map(|constraint| vec![format!("{constraint} is not satisfied")]),
+        };
+
+        Ok(Replay {
+            error,
+            witness: Some(witness),
+            logs,
+            captured_error: self.manifest.error.clone(),
+        })
+    }
+}
+
+impl Drop for Bundle {
+    fn drop(&mut self) {
+        if self.extracted {
+            if let Err(err) = fs::remove_dir_all(&self.dir) {
+                warn!("could not clean up {}: {err}", self.dir.display());
+            }
+        }
+    }
+}
+
+/// Calculates the witness for the given inputs with the module `wasm`, collecting the messages of the circuit.
+fn calculate_witness<F: PrimeFieldBits>(
+    wasm: &Path,
+    inputs: Vec<CircomInput<F>>,
+    sanity_check: bool,
+) -> (Result<Vec<F>, WitnessCalculatorError>, Vec<String>) {
+    let mut wtns = match WitnessCalculator::new(wasm) {
+        Ok(wtns) => wtns,
+        Err(err) => return (Err(err), vec![]),
+    };
+    wtns.set_log_sink(LogSink::Collect);
+    let witness = wtns.calculate_witness(inputs, sanity_check);
+    (witness, wtns.take_logs())
+}
+
+/// Checks that the bundled file `name` has the given hash, if any.
+fn check_file(dir: &Path, name: &str, sha256: Option<&String>) -> Result<(), BundleError> {
+    let Some(sha256) = sha256 else {
+        return Ok(());
+    };
+    if &sha256_hex(&fs::read(dir.join(name))?) != sha256 {
+        return Err(CorruptedFile {
+            file: name.to_string(),
+        });
+    }
+    Ok(())
+}
+
+/// Returns the message of `error` followed by the messages of its sources.
+fn error_chain(error: &(dyn Error + 'static)) -> Vec<String> {
+    std::iter::successors(Some(error), |&error| error.source())
+        .map(|error| error.to_string())
+        .collect()
+}
+
+/// Returns the version of wasmer witnesses are calculated with, if any.
+fn wasmer_version() -> Option<String> {
+    #[cfg(feature = "wasmer")]
+    return Some(wasmer::VERSION.to_string());
+    #[cfg(not(feature = "wasmer"))]
+    return None;
+}
+
+fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use ff::Field;
+    use pasta_curves::{pallas, vesta};
+
+    use super::*;
+    use crate::reader::tests::r1cs_bytes_with;
+    use crate::witness::Goldilocks;
+
+    const SHA256: &str = "circom/sha256/circom_sha256.wasm";
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("circom-scotia-{name}-{}", std::process::id()))
+    }
+
+    #[test]
+    fn test_witness_bundle() {
+        let wasm = Path::new(SHA256);
+        let inputs = vec![CircomInput::new("arg_in".into(), vec![vesta::Base::ONE; 3])];
+        let mut wtns = WitnessCalculator::new(wasm).unwrap();
+        let err = wtns.calculate_witness(inputs.clone(), true).unwrap_err();
+
+        let dir = temp_path("witness-bundle");
+        let manifest = BugReport::witness(wasm, &inputs, true)
+            .write_dir(&dir, &err)
+            .unwrap();
+        assert_eq!(manifest.kind, RunKind::Witness);
+        assert_eq!(manifest.error, [err.to_string()]);
+        assert_eq!(
+            manifest.inputs,
+            [InputShape {
+                name: "arg_in".into(),
+                len: 3
+            }]
+        );
+
+        let bundle = Bundle::open(&dir).unwrap();
+        assert_eq!(bundle.manifest, manifest);
+        let bundled = bundle.inputs::<vesta::Base>().unwrap().unwrap();
+        assert_eq!(bundled.len(), 1);
+        assert_eq!(bundled[0].name, inputs[0].name);
+        assert_eq!(bundled[0].value, inputs[0].value);
+        let replay = bundle.replay::<vesta::Base>().unwrap();
+        assert!(replay.reproduced());
+        assert_eq!(replay.error.as_ref(), Some(&manifest.error));
+        assert!(matches!(
+            bundle.replay::<pallas::Base>().unwrap_err(),
+            BundleError::NonMatchingField { .. }
+        ));
+
+        // Fixed inputs no longer reproduce the failure.
+        let fixed = vec![CircomInput::new("arg_in".into(), vec![vesta::Base::ONE; 2])];
+        let replay = bundle.replay_with_inputs(fixed).unwrap();
+        assert!(!replay.reproduced());
+        assert_eq!(replay.witness.unwrap().len(), 29823);
+
+        // A replay failing with another error does not reproduce the captured failure.
+        let other = WitnessCalculatorError::UnexpectedReturn("test".into());
+        BugReport::witness(wasm, &inputs, true)
+            .write_dir(&dir, &other)
+            .unwrap();
+        let replay = Bundle::open(&dir).unwrap().replay::<vesta::Base>().unwrap();
+        assert_eq!(replay.error.as_ref(), Some(&manifest.error));
+        assert!(!replay.reproduced());
+
+        // A redacted bundle written over it leaves none of its inputs behind.
+        BugReport::witness(wasm, &inputs, true)
+            .redact_inputs()
+            .write_dir(&dir, &err)
+            .unwrap();
+        assert!(!dir.join(INPUTS).exists());
+        assert!(Bundle::open(&dir).unwrap().manifest.redacted);
+
+        // A bundle whose circuit changed is rejected.
+        fs::write(dir.join(WASM), b"\0asm").unwrap();
+        assert!(matches!(
+            Bundle::open(&dir).unwrap_err(),
+            BundleError::CorruptedFile { file } if file == WASM
+        ));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_synthesis_bundle() {
+        let wasm = Path::new(SHA256);
+        let inputs = vec![CircomInput::new(
+            "arg_in".into(),
+            vec![vesta::Base::ZERO; 2],
+        )];
+        let witness = WitnessCalculator::new(wasm)
+            .unwrap()
+            .calculate_witness(inputs.clone(), true)
+            .unwrap();
+
+        // The output of the circuit is constrained to be zero, which the witness does not satisfy.
+        let r1cs = temp_path("bundle.r1cs");
+        let constraint = (
+            vec![(1, vesta::Base::ONE)],
+            vec![(0, vesta::Base::ONE)],
+            vec![],
+        );
+        fs::write(
+            &r1cs,
+            r1cs_bytes_with(witness.len() as u32, 1, 2, &[constraint]),
+        )
+        .unwrap();
+
+        let err = WitnessCalculatorError::UnexpectedReturn("test".into());
+        let tar = temp_path("synthesis-bundle.tar");
+        let logs = vec!["proving failed".to_string()];
+        BugReport::synthesis(wasm, &r1cs, &inputs, true)
+            .with_witness(&witness)
+            .with_logs(&logs)
+            .write_tar(&tar, &err)
+            .unwrap();
+
+        let bundle = Bundle::open(&tar).unwrap();
+        assert_eq!(bundle.manifest.kind, RunKind::Synthesis);
+        assert!(bundle.dir.join(WITNESS).exists());
+        assert_eq!(
+            fs::read_to_string(bundle.dir.join(LOGS)).unwrap(),
+            "proving failed"
+        );
+        let replay = bundle.replay::<vesta::Base>().unwrap();
+        assert_eq!(
+            replay.error,
+            Some(vec!["constraint 0 is not satisfied".into()])
+        );
+        assert!(!replay.reproduced());
+        assert_eq!(replay.witness, Some(witness.clone()));
+
+        // The extraction directory is removed with the bundle.
+        let extracted = bundle.dir.clone();
+        drop(bundle);
+        assert!(!extracted.exists());
+
+        // Redacted bundles hold neither the inputs nor the witness.
+        let redacted = BugReport::synthesis(wasm, &r1cs, &inputs, true)
+            .with_witness(&witness)
+            .redact_inputs()
+            .write_tar(&tar, &err)
+            .unwrap();
+        assert!(redacted.redacted);
+        let bundle = Bundle::open(&tar).unwrap();
+        assert!(!bundle.dir.join(INPUTS).exists());
+        assert!(!bundle.dir.join(WITNESS).exists());
+        assert!(bundle.inputs::<vesta::Base>().unwrap().is_none());
+        assert!(matches!(
+            bundle.replay::<vesta::Base>().unwrap_err(),
+            BundleError::RedactedInputs
+        ));
+        assert!(bundle
+            .replay_with_inputs(inputs.clone())
+            .unwrap()
+            .error
+            .is_some());
+
+        // The witness is calculated again when it is not recorded.
+        BugReport::synthesis(wasm, &r1cs, &inputs, true)
+            .write_tar(&tar, &err)
+            .unwrap();
+        let bundle = Bundle::open(&tar).unwrap();
+        assert!(bundle.dir.join(WITNESS).exists());
+        assert_eq!(
+            bundle.replay::<vesta::Base>().unwrap().witness,
+            Some(witness)
+        );
+        drop(bundle);
+
+        fs::remove_file(tar).unwrap();
+        fs::remove_file(r1cs).unwrap();
+    }
+
+    #[test]
+    fn test_collected_logs() {
+        let wasm = Path::new("circom/mock/adder_goldilocks.wat");
+        let inputs = vec![
+            CircomInput::new("a".into(), vec![Goldilocks::ONE]),
+            CircomInput::new("b".into(), vec![Goldilocks::from(2)]),
+        ];
+        let err = WitnessCalculatorError::UnexpectedReturn("test".into());
+
+        let dir = temp_path("logs-bundle");
+        BugReport::witness(wasm, &inputs, true)
+            .write_dir(&dir, &err)
+            .unwrap();
+        let logs = fs::read_to_string(dir.join(LOGS)).unwrap();
+        assert!(logs.starts_with("a + b ="));
+
+        // Recorded messages are bundled as they are.
+        BugReport::witness(wasm, &inputs, true)
+            .with_logs(&[])
+            .write_dir(&dir, &err)
+            .unwrap();
+        assert!(!dir.join(LOGS).exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,5 +1,7 @@
 use thiserror::Error;
 
+use crate::witness::WitnessCalculatorError;
+
 /// Enum related to error happening while reading data from source.
 #[derive(Error, Debug)]
 pub enum ReaderError {
@@ -74,6 +76,13 @@ pub enum ReaderError {
     /// Error thrown if the specified modulus in the r1cs header is not the one we were expecting.
     #[error("Mismatched prime field. Expected {expected}, read {value} in the header instead.")]
     NonMatchingPrime { expected: String, value: String },
+    /// High level error returned if we could not read our .sym file.
+    #[error("Failed to read symbols from file \"{filename}\": {source}")]
+    ReadSymbolsError {
+        filename: String,
+        #[source]
+        source: Box<dyn std::error::Error + Sync + Send>,
+    },
     /// Error thrown when parsing wires in an R1CS file. We expect the first wire to always be mapped to 0.
     #[error("Wire 0 should always be mapped to 0")]
     WireError,
@@ -100,12 +109,76 @@ pub enum WitnessError {
         #[source]
         source: Box<dyn std::error::Error + Sync + Send>,
     },
-    /// Error generated if a panic occurs when trying to access the content of our Mutex.
-    #[error("Could not acquire the witness calculator mutex lock.")]
-    MutexError,
+    /// Error if we could not parse the inputs of the circuit.
+    #[error("Failed to parse the circuit inputs: {source}")]
+    InputError {
+        #[source]
+        source: Box<dyn std::error::Error + Sync + Send>,
+    },
     /// Error if we could not calculate the witness.
     #[error("Failed to calculate the witness: {source}")]
     WitnessCalculationError {
+        #[source]
+        source: WitnessCalculatorError,
+    },
+}
+
+/// Error related to looking up the value of a signal in a witness.
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum SignalError {
+    /// Error if no signal of the symbol table has the given name.
+    #[error("Signal \"{name}\" not found in the symbol table.")]
+    NotFound { name: String },
+    /// Error if the signal was removed by the compiler when simplifying constraints, so that it is not
+    /// part of the witness.
+    #[error("Signal \"{name}\" was removed from the witness by constraint simplification.")]
+    Removed { name: String },
+    /// Error if the symbol table maps the signal to a wire past the end of the witness, which happens
+    /// when the symbols and the witness come from different circuits.
+    #[error("Signal \"{name}\" is mapped to wire {wire}, but the witness holds {len} wires.")]
+    WireOutOfRange {
+        name: String,
+        wire: usize,
+        len: usize,
+    },
+    /// Error if an array signal is bound to a number of variables that differs from its length.
+    #[error("Array signal \"{name}\" has {expected} elements, but {actual} bindings were given.")]
+    LengthMismatch {
+        name: String,
+        expected: usize,
+        actual: usize,
+    },
+}
+
+/// Error related to capturing or replaying bug-report bundles.
+#[cfg(feature = "bundle")]
+#[derive(Error, Debug)]
+pub enum BundleError {
+    /// Error generated while trying to read or write the files of a bundle.
+    #[error("Could not access the bundle files: {source}")]
+    FileSystemError {
+        #[from]
+        source: std::io::Error,
+    },
+    /// Error if the manifest of a bundle cannot be written or read.
+    #[error("Invalid bundle manifest: {source}")]
+    ManifestError {
+        #[from]
+        source: serde_json::Error,
+    },
+    /// Error if the contents of a file of the bundle do not match the hash recorded in its manifest.
+    #[error("The bundled file \"{file}\" does not match its recorded hash.")]
+    CorruptedFile { file: String },
+    /// Error if a bundle is replayed for a field other than the field of its run.
+    #[error("The bundle was captured for the field {expected}, not {actual}.")]
+    NonMatchingField { expected: String, actual: String },
+    /// Error if the inputs of a bundle were redacted, so that the run cannot be replayed without them.
+    #[error("The inputs of the bundle were redacted, they must be provided to replay it.")]
+    RedactedInputs,
+    /// Error if the bundled inputs or files of the circuit cannot be loaded.
+    #[error("Could not load the bundled {file}: {source}")]
+    LoadError {
+        file: String,
         #[source]
         source: Box<dyn std::error::Error + Sync + Send>,
     },
@@ -121,7 +194,7 @@ pub enum CircomConfigError {
     WitnessCalculatorInstantiationError {
         path: String,
         #[source]
-        source: Box<dyn std::error::Error + Sync + Send>,
+        source: WitnessCalculatorError,
     },
     /// Error if we could not load data from our R1CS file.
     #[error("Could load r1cs data from the given file \"{path}\": {source}")]
@@ -30,21 +30,33 @@
 //! and [ark-circom](https://github.com/gakonst/ark-circom) for their foundational work that this library builds upon.
 
 use crate::error::WitnessError::{
-    self, FailedExecutionError, FileSystemError, LoadWitnessError, MutexError,
+    self, FailedExecutionError, FileSystemError, InputError, LoadWitnessError,
     WitnessCalculationError,
 };
 use crate::r1cs::CircomInput;
-use crate::reader::load_witness_from_file;
+use crate::reader::{load_inputs_from_json, load_witness_from_file, write_witness_to_bin_file};
+use crate::witness::{Circom, WitnessCalculator};
 use anyhow::Result;
-use bellpepper_core::{num::AllocatedNum, ConstraintSystem, LinearCombination, SynthesisError};
+use bellpepper_core::{
+    num::AllocatedNum, ConstraintSystem, LinearCombination, SynthesisError, Variable,
+};
 use ff::{PrimeField, PrimeFieldBits};
 use log::{error, info, warn};
-use r1cs::{CircomConfig, R1CS};
+use r1cs::{CircomConfig, CircomSynthesized, Constraint, SignalHandle, SynthesisOptions, R1CS};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
 use std::env::current_dir;
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::process::Command;
 
+#[cfg(not(any(feature = "wasmer", feature = "wasmi")))]
+compile_error!(
+    "circom-scotia runs circuits with wasmer or wasmi: enable the `wasmer` or `wasmi` feature"
+);
+
+#[cfg(feature = "bundle")]
+pub mod bundle;
 pub mod error;
 pub mod r1cs;
 pub mod reader;
@@ -67,7 +79,8 @@ pub mod witness;
 ///
 /// # Errors
 ///
-/// Returns an error if any file operations fail, or if the WASM execution fails.
+/// Returns an error if any file operations fail, if the WASM execution fails, or with a
+/// [`LoadWitnessError`] if the generated witness is not over the field `F`.
 ///
 /// # Examples
 ///
@@ -82,7 +95,7 @@ pub mod witness;
 /// let result = generate_witness_from_wasm::<Fr>(witness_dir, input_json, &witness_output);
 /// ```
 #[deprecated(
-    note = "generate_witness_from_wasm would use a node CLI to calculate witness. We now expect users to use calculate_witness directly."
+    note = "generate_witness_from_wasm would use a node CLI to calculate witness. We now expect users to use calculate_witness directly, or generate_witness_from_json with the same files."
 )]
 pub fn generate_witness_from_wasm<F: PrimeField>(
     witness_dir: PathBuf,
@@ -129,10 +142,66 @@ pub fn generate_witness_from_wasm<F: PrimeField>(
     load_witness_from_file(witness_output).map_err(|err| LoadWitnessError { source: err.into() })
 }
 
+/// Generates a witness from the WebAssembly (WASM) binary of a circuit using a JSON input, without
+/// node.
+///
+/// This function is a replacement for [`generate_witness_from_wasm`] taking the same files: the
+/// witness is calculated in-process from the `main.wasm` binary found in `witness_dir`, and written
+/// to `witness_output`, if given, as a binary `.wtns` file. No file is written to the current
+/// directory, so that concurrent calls do not interfere.
+///
+/// # Arguments
+///
+/// * `witness_dir` - The directory containing the `main.wasm` binary generated by circom.
+/// * `witness_input_json` - The JSON input of the circuit, mapping the name of each input signal to
+///   its value.
+/// * `witness_output` - The path where the witness file will be stored, if any.
+/// * `sanity_check` - A boolean indicating whether a sanity check should be performed.
+///
+/// # Errors
+///
+/// Returns an error if the JSON input cannot be parsed, if the witness calculation fails, or if the
+/// witness file cannot be written.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use std::path::Path;
+/// # use circom_scotia::generate_witness_from_json;
+/// # use pasta_curves::vesta::Base as Fr;
+///
+/// let input_json = "{\"input\": \"42\"}";
+/// let witness_output = Path::new("output.wtns");
+/// let result = generate_witness_from_json::<Fr>("./path/to/witness/dir", input_json, Some(witness_output), true);
+/// ```
+pub fn generate_witness_from_json<F: PrimeFieldBits>(
+    witness_dir: impl AsRef<Path>,
+    witness_input_json: &str,
+    witness_output: Option<&Path>,
+    sanity_check: bool,
+) -> Result<Vec<F>, WitnessError> {
+    let input = load_inputs_from_json(witness_input_json.as_bytes())
+        .map_err(|err| InputError { source: err.into() })?;
+
+    let witness = WitnessCalculator::new(witness_dir.as_ref().join("main.wasm"))
+        .and_then(|mut witness_calculator| {
+            witness_calculator.calculate_witness(input, sanity_check)
+        })
+        .map_err(|err| WitnessCalculationError { source: err })?;
+
+    if let Some(witness_output) = witness_output {
+        write_witness_to_bin_file(witness_output, &witness)
+            .map_err(|err| FileSystemError { source: err.into() })?;
+    }
+
+    Ok(witness)
+}
+
 /// Calculates a witness for a given R1CS configuration and a set of circuit inputs.
 ///
-/// The function locks the global witness calculation instance and then calculates
-/// the witness based on the inputs provided. It performs a sanity check if required.
+/// The function checks out a witness calculator from the pool of the configuration and then
+/// calculates the witness based on the inputs provided. It performs a sanity check if required.
+/// Concurrent calls run on separate calculators, up to the size of the pool.
 ///
 /// # Arguments
 ///
@@ -142,7 +211,8 @@ pub fn generate_witness_from_wasm<F: PrimeField>(
 ///
 /// # Errors
 ///
-/// Returns an error if witness calculation fails or if the mutex lock cannot be acquired.
+/// Returns an error if witness calculation fails or if a new witness calculator cannot be
+/// instantiated.
 ///
 /// # Examples
 ///
@@ -160,15 +230,71 @@ pub fn generate_witness_from_wasm<F: PrimeField>(
 /// let result = calculate_witness::<Fr>(&cfg, inputs, true);
 /// ```
 pub fn calculate_witness<F: PrimeFieldBits>(
-    cfg: &CircomConfig<F>,
+    cfg: &CircomConfig<F, impl Circom>,
     input: Vec<CircomInput<F>>,
     sanity_check: bool,
 ) -> Result<Vec<F>, WitnessError> {
-    let mut lock = cfg.wtns.lock().map_err(|_| MutexError)?;
-    let witness_calculator = &mut *lock;
-    witness_calculator
-        .calculate_witness(input, sanity_check)
-        .map_err(|err| WitnessCalculationError { source: err.into() })
+    cfg.wtns
+        .get()
+        .and_then(|mut witness_calculator| {
+            witness_calculator.calculate_witness(input, sanity_check)
+        })
+        .map_err(|err| WitnessCalculationError { source: err })
+}
+
+/// Calculates the witnesses for many sets of circuit inputs with the same R1CS configuration.
+///
+/// The function checks out a single witness calculator from the pool of the configuration, which is
+/// reused for every set of inputs and returned to the pool once the iterator is dropped. Witnesses
+/// are calculated lazily as the iterator is advanced, and each one reports its own error without
+/// aborting the rest of the batch.
+///
+/// # Arguments
+///
+/// * `cfg` - A reference to the [`CircomConfig`] containing R1CS configuration.
+/// * `inputs` - The sets of [`CircomInput`], each representing the inputs to the circuit.
+/// * `sanity_check` - A boolean indicating whether a sanity check should be performed.
+///
+/// # Errors
+///
+/// Returns an error if a witness calculator cannot be checked out from the pool.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use std::path::PathBuf;
+/// # use circom_scotia::{calculate_witnesses, r1cs::{CircomConfig, CircomInput}};
+/// # use pasta_curves::vesta::Base as Fr;
+///
+/// let cfg: CircomConfig<Fr> = CircomConfig::new("circuit.wasm", "circuit.r1cs").unwrap();
+/// let inputs = (0..10u64).map(|i| vec![CircomInput::new(String::from("step_in"), vec![Fr::from(i)])]);
+/// for witness in calculate_witnesses(&cfg, inputs, true).unwrap() {
+///     let witness = witness.unwrap();
+/// }
+/// ```
+pub fn calculate_witnesses<'a, F, I>(
+    cfg: &'a CircomConfig<F, impl Circom>,
+    inputs: I,
+    sanity_check: bool,
+) -> Result<impl Iterator<Item = Result<Vec<F>, WitnessError>> + 'a, WitnessError>
+where
+    F: PrimeFieldBits,
+    I: IntoIterator<Item = Vec<CircomInput<F>>>,
+    I::IntoIter: 'a,
+{
+    let mut witness_calculator = cfg
+        .wtns
+        .get()
+        .map_err(|err| WitnessCalculationError { source: err })?;
+    let mut inputs = inputs.into_iter();
+    Ok(std::iter::from_fn(move || {
+        let input = inputs.next()?;
+        Some(
+            witness_calculator
+                .calculate_witness(input, sanity_check)
+                .map_err(|err| WitnessCalculationError { source: err }),
+        )
+    }))
 }
 
 /// Synthesizes the constraint system based on the R1CS and the witness data.
@@ -176,6 +302,10 @@ pub fn calculate_witness<F: PrimeFieldBits>(
 /// This function updates the provided constraint system based on the R1CS constraints
 /// and the witness data. It returns the public outputs of the circuit as [`AllocatedNum`].
 ///
+/// Every signal, including public ones, is allocated as a private variable of the constraint
+/// system. See [`synthesize_with`] to allocate public signals as public inputs, reuse variables of
+/// the constraint system, or name the signals.
+///
 /// # Arguments
 ///
 /// * `cs` - A mutable reference to the constraint system.
@@ -194,65 +324,621 @@ pub fn synthesize<F: PrimeField, CS: ConstraintSystem<F>>(
     r1cs: R1CS<F>,
     witness: Option<Vec<F>>,
 ) -> Result<Vec<AllocatedNum<F>>, SynthesisError> {
-    let witness = &witness;
-    let mut vars: Vec<AllocatedNum<F>> = vec![];
-
-    // Retrieve all our public signals (inputs and outputs).
-    for i in 1..r1cs.num_inputs {
-        let f: F = {
-            match witness {
-                None => F::ONE,
-                Some(w) => w[i],
-            }
-        };
-        let v = AllocatedNum::alloc(cs.namespace(|| format!("public_{}", i)), || Ok(f))?;
+    let signals = synthesize_with(cs, r1cs, witness, SynthesisOptions::new())?;
+    Ok(signals
+        .outputs
+        .into_iter()
+        .map(|signal| signal.num)
+        .collect())
+}
 
-        vars.push(v);
+/// Synthesizes the constraint system based on the R1CS and the witness data, allocating the
+/// signals of the circuit as set by `options`, and returns all of them.
+///
+/// Circom public signals are the outputs of the circuit followed by its public inputs. Those
+/// selected by [`SynthesisOptions::public_signals`] are allocated with
+/// [`AllocatedNum::alloc_input`], so that proofs over the constraint system expose them, and the
+/// others with [`AllocatedNum::alloc`]. Signals bound by [`SynthesisOptions::bindings`] reuse
+/// variables of the constraint system, and [`SynthesisOptions::symbols`] names the signals, their
+/// variables and the constraints.
+///
+/// # Arguments
+///
+/// * `cs` - A mutable reference to the constraint system.
+/// * `r1cs` - The [`R1CS`] data structure.
+/// * `witness` - An optional vector of field elements representing the witness data.
+/// * `options` - How to allocate the signals of the circuit.
+///
+/// # Errors
+///
+/// Returns a [`SynthesisError`] if constraint synthesis fails.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use bellpepper_core::test_cs::TestConstraintSystem;
+/// # use circom_scotia::{reader::load_sym, r1cs::{CircomConfig, PublicSignals, SynthesisOptions}, synthesize_with};
+/// # use pasta_curves::vesta::Base as Fr;
+/// let cfg: CircomConfig<Fr> = CircomConfig::new("circuit.wasm", "circuit.r1cs").unwrap();
+/// let symbols = load_sym("circuit.sym").unwrap();
+/// let mut cs = TestConstraintSystem::<Fr>::new();
+/// let options = SynthesisOptions::new()
+///     .public_signals(PublicSignals::All)
+///     .symbols(&symbols);
+/// let signals = synthesize_with(&mut cs, cfg.r1cs.clone(), None, options).unwrap();
+/// let hash = signals.get("main.hasher.out[0]").unwrap();
+/// ```
+pub fn synthesize_with<F: PrimeField, CS: ConstraintSystem<F>>(
+    cs: &mut CS,
+    r1cs: R1CS<F>,
+    witness: Option<Vec<F>>,
+    options: SynthesisOptions<'_, F>,
+) -> Result<CircomSynthesized<F>, SynthesisError> {
+    let SynthesisOptions {
+        public_signals,
+        mut bindings,
+        symbols,
+    } = options;
+    let num_pub_out = r1cs.num_pub_out;
+    let num_inputs = r1cs.num_inputs;
+    let num_prv_in = r1cs.num_prv_in.min(r1cs.num_aux);
+
+    // Signals sharing a wire take the name listed first.
+    let mut wire_names = BTreeMap::new();
+    let mut wires_by_name = BTreeMap::new();
+    for symbol in symbols.iter().flat_map(|symbols| &symbols.symbols) {
+        if let Some(wire) = symbol.wire {
+            wire_names.entry(wire).or_insert(symbol.name.as_str());
+            wires_by_name.insert(symbol.name.clone(), wire);
+        }
     }
 
-    // Retrieve all private traces.
-    for i in 0..r1cs.num_aux {
-        let f: F = {
-            match witness {
-                None => F::ONE,
-                Some(w) => w[i + r1cs.num_inputs],
+    let labels = Labels {
+        names: &wire_names,
+        num_inputs,
+    };
+
+    let mut signals = CircomSynthesized {
+        outputs: vec![],
+        public_inputs: vec![],
+        private_inputs: vec![],
+        aux: vec![],
+        wires: wires_by_name,
+    };
+    let mut wires = Vec::with_capacity(r1cs.num_variables);
+    for i in 1..r1cs.num_inputs + r1cs.num_aux {
+        let value = || Ok(witness.as_ref().map_or(F::ONE, |w| w[i]));
+        let public = i < num_inputs && public_signals.is_public(i);
+        let alloc = |cs: &mut CS| {
+            let namespace = cs.namespace(|| labels.get(i));
+            if public {
+                AllocatedNum::alloc_input(namespace, value)
+            } else {
+                AllocatedNum::alloc(namespace, value)
+            }
+        };
+
+        let num = match bindings.take(i) {
+            Some(lc) => {
+                let num = if i <= num_pub_out || public {
+                    let v = alloc(cs)?;
+                    cs.enforce(
+                        || format!("{} binding", labels.get(i)),
+                        |_| lc.clone(),
+                        |lc| lc + CS::one(),
+                        |lc| lc + v.get_variable(),
+                    );
+                    Some(v)
+                } else {
+                    None
+                };
+                wires.push(Wire::Bound(lc));
+                num
+            }
+            None => {
+                let v = alloc(cs)?;
+                wires.push(Wire::Variable(v.get_variable()));
+                Some(v)
             }
         };
 
-        let v = AllocatedNum::alloc(cs.namespace(|| format!("aux_{}", i)), || Ok(f))?;
-        vars.push(v);
+        let Some(num) = num else {
+            continue;
+        };
+        let handle = SignalHandle {
+            wire: i,
+            name: wire_names.get(&i).map(|name| name.to_string()),
+            public,
+            num,
+        };
+        if i <= num_pub_out {
+            signals.outputs.push(handle);
+        } else if i < num_inputs {
+            signals.public_inputs.push(handle);
+        } else if i < num_inputs + num_prv_in {
+            signals.private_inputs.push(handle);
+        } else {
+            signals.aux.push(handle);
+        }
     }
 
-    // Public output to return.
-    let output = match r1cs.num_pub_out {
-        0 => vec![],
-        1 => vec![vars[0].clone()],
-        _ => vars[0..r1cs.num_pub_out].to_vec(),
-    };
+    enforce_constraints(
+        cs,
+        r1cs.constraints,
+        &wires,
+        symbols.is_some().then_some(&labels),
+    );
+
+    Ok(signals)
+}
+
+/// A signal of a circuit in the constraint system: the variable allocated for it, or the linear
+/// combination it is bound to.
+enum Wire<F: PrimeField> {
+    Variable(Variable),
+    Bound(LinearCombination<F>),
+}
+
+/// Labels of the signals at wires `1..`: the name listed first in the symbol table, falling back to
+/// the `public_{i}` and `aux_{i}` namespaces.
+struct Labels<'a> {
+    names: &'a BTreeMap<usize, &'a str>,
+    num_inputs: usize,
+}
+
+impl<'a> Labels<'a> {
+    fn get(&self, wire: usize) -> Cow<'a, str> {
+        match self.names.get(&wire) {
+            Some(name) => Cow::Borrowed(name),
+            None if wire < self.num_inputs => Cow::Owned(format!("public_{}", wire)),
+            None => Cow::Owned(format!("aux_{}", wire - self.num_inputs)),
+        }
+    }
+}
 
+/// Enforces the constraints of a circuit, given the signals at wires `1..`.
+///
+/// Constraints are named `constraint {i}`, followed by the labels of the signals they involve when
+/// `labels` are given. Names are only built when the constraint system asks for them.
+fn enforce_constraints<F: PrimeField, CS: ConstraintSystem<F>>(
+    cs: &mut CS,
+    constraints: Vec<Constraint<F>>,
+    wires: &[Wire<F>],
+    labels: Option<&Labels<'_>>,
+) {
     // Create closure responsible to create the linear combination data.
-    let make_lc = |lc_data: Vec<(usize, F)>| {
+    let make_lc = |lc_data: &[(usize, F)]| {
         let res = lc_data.iter().fold(
             LinearCombination::<F>::zero(),
             |lc: LinearCombination<F>, (index, coeff)| {
-                lc + if *index > 0 {
-                    (*coeff, vars[*index - 1].get_variable())
-                } else {
-                    (*coeff, CS::one())
+                if *index == 0 {
+                    return lc + (*coeff, CS::one());
+                }
+                match &wires[*index - 1] {
+                    Wire::Variable(variable) => lc + (*coeff, *variable),
+                    Wire::Bound(bound) => lc + (*coeff, bound),
                 }
             },
         );
         res
     };
 
-    for (i, constraint) in r1cs.constraints.into_iter().enumerate() {
+    for (i, constraint) in constraints.into_iter().enumerate() {
         cs.enforce(
-            || format!("constraint {}", i),
-            |_| make_lc(constraint.0),
-            |_| make_lc(constraint.1),
-            |_| make_lc(constraint.2),
+            || match labels {
+                Some(labels) => constraint_name(i, &constraint, labels),
+                None => format!("constraint {}", i),
+            },
+            |_| make_lc(&constraint.0),
+            |_| make_lc(&constraint.1),
+            |_| make_lc(&constraint.2),
+        );
+    }
+}
+
+/// Maximal number of signals listed in the name of a constraint.
+const MAX_CONSTRAINT_SIGNALS: usize = 4;
+
+/// Names the constraint `i` after the signals it involves, in order of appearance, such as
+/// `constraint 3: main.a, main.b, main.c`.
+fn constraint_name<F: PrimeField>(
+    i: usize,
+    constraint: &Constraint<F>,
+    labels: &Labels<'_>,
+) -> String {
+    let mut wires: Vec<usize> = vec![];
+    for &(wire, _) in constraint
+        .0
+        .iter()
+        .chain(&constraint.1)
+        .chain(&constraint.2)
+    {
+        if wire > 0 && !wires.contains(&wire) {
+            wires.push(wire);
+        }
+    }
+
+    let mut name = format!("constraint {}:", i);
+    for (j, wire) in wires.iter().take(MAX_CONSTRAINT_SIGNALS).enumerate() {
+        let separator = if j == 0 { " " } else { ", " };
+        name.push_str(separator);
+        name.push_str(&labels.get(*wire));
+    }
+    if wires.len() > MAX_CONSTRAINT_SIGNALS {
+        name.push_str(&format!(
+            " and {} more",
+            wires.len() - MAX_CONSTRAINT_SIGNALS
+        ));
+    }
+    name
+}
+
+#[cfg(test)]
+mod tests {
+    use ff::Field;
+    use pasta_curves::vesta;
+
+    use super::*;
+
+    #[test]
+    fn test_generate_witness_from_json() {
+        let witness_dir =
+            std::env::temp_dir().join(format!("circom-scotia-witness-dir-{}", std::process::id()));
+        fs::create_dir_all(&witness_dir).unwrap();
+        fs::copy(
+            "circom/sha256/circom_sha256.wasm",
+            witness_dir.join("main.wasm"),
+        )
+        .unwrap();
+        let witness_output = witness_dir.join("output.wtns");
+
+        let witness = generate_witness_from_json::<vesta::Base>(
+            &witness_dir,
+            r#"{"arg_in": ["0", 1]}"#,
+            Some(&witness_output),
+            true,
+        )
+        .unwrap();
+
+        let input = vec![CircomInput::new(
+            "arg_in".into(),
+            vec![vesta::Base::ZERO, vesta::Base::ONE],
+        )];
+        let mut witness_calculator = WitnessCalculator::new(witness_dir.join("main.wasm")).unwrap();
+        assert_eq!(
+            witness,
+            witness_calculator.calculate_witness(input, true).unwrap()
+        );
+        assert_eq!(
+            load_witness_from_file::<vesta::Base>(&witness_output).unwrap(),
+            witness
+        );
+        assert!(!current_dir().unwrap().join("circom_input.json").exists());
+
+        let err =
+            generate_witness_from_json::<vesta::Base>(&witness_dir, "{\"arg_in\": ", None, true)
+                .unwrap_err();
+        assert!(matches!(err, WitnessError::InputError { .. }));
+
+        fs::remove_dir_all(witness_dir).unwrap();
+    }
+
+    #[test]
+    fn test_public_signals() {
+        use crate::r1cs::PublicSignals;
+        use bellpepper_core::test_cs::TestConstraintSystem;
+        use std::collections::BTreeSet;
+
+        // out = in * secret, with one output, one public input and one private input.
+        let r1cs = R1CS {
+            num_pub_in: 1,
+            num_pub_out: 1,
+            num_prv_in: 1,
+            num_inputs: 3,
+            num_aux: 1,
+            num_variables: 4,
+            constraints: vec![(
+                vec![(2, vesta::Base::ONE)],
+                vec![(3, vesta::Base::ONE)],
+                vec![(1, vesta::Base::ONE)],
+            )],
+        };
+        let witness = [1, 42, 6, 7].map(vesta::Base::from).to_vec();
+
+        for (public_signals, expected) in [
+            (PublicSignals::None, [false, false]),
+            (PublicSignals::All, [true, true]),
+            (PublicSignals::Only(BTreeSet::from([2, 5])), [false, true]),
+        ] {
+            let mut cs = TestConstraintSystem::<vesta::Base>::new();
+            let options = SynthesisOptions::new().public_signals(public_signals);
+            let signals =
+                synthesize_with(&mut cs, r1cs.clone(), Some(witness.clone()), options).unwrap();
+            assert!(cs.is_satisfied());
+
+            let public = |handles: &[SignalHandle<vesta::Base>]| {
+                handles
+                    .iter()
+                    .map(|handle| (handle.wire, handle.public))
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(public(&signals.outputs), [(1, expected[0])]);
+            assert_eq!(public(&signals.public_inputs), [(2, expected[1])]);
+            assert_eq!(public(&signals.private_inputs), [(3, false)]);
+            assert_eq!(
+                signals.outputs[0].num.get_value(),
+                Some(vesta::Base::from(42))
+            );
+            let public = expected.iter().filter(|&&public| public).count();
+            assert_eq!(cs.num_inputs(), 1 + public);
+        }
+
+        let mut cs = TestConstraintSystem::<vesta::Base>::new();
+        let outputs = synthesize(&mut cs, r1cs, Some(witness)).unwrap();
+        assert_eq!(outputs.len(), 1);
+        assert_eq!(cs.num_inputs(), 1);
+    }
+
+    #[test]
+    fn test_bindings() {
+        use crate::error::SignalError;
+        use crate::r1cs::{Bindings, PublicSignals, Symbol, SymbolTable};
+        use bellpepper_core::{test_cs::TestConstraintSystem, Comparable};
+
+        // out = in * secret, with one output, one public input and one private input.
+        let r1cs = R1CS {
+            num_pub_in: 1,
+            num_pub_out: 1,
+            num_prv_in: 1,
+            num_inputs: 3,
+            num_aux: 1,
+            num_variables: 4,
+            constraints: vec![(
+                vec![(2, vesta::Base::ONE)],
+                vec![(3, vesta::Base::ONE)],
+                vec![(1, vesta::Base::ONE)],
+            )],
+        };
+        let symbols = SymbolTable {
+            symbols: ["main.out", "main.in", "main.secret[0]"]
+                .into_iter()
+                .enumerate()
+                .map(|(i, name)| Symbol {
+                    label: i + 1,
+                    wire: Some(i + 1),
+                    component: 0,
+                    name: name.to_string(),
+                })
+                .collect(),
+        };
+        let witness = [1, 42, 6, 7].map(vesta::Base::from).to_vec();
+
+        let mut cs = TestConstraintSystem::<vesta::Base>::new();
+        let x = AllocatedNum::alloc(cs.namespace(|| "x"), || Ok(vesta::Base::from(6))).unwrap();
+        let y = AllocatedNum::alloc(cs.namespace(|| "y"), || Ok(vesta::Base::from(5))).unwrap();
+        // secret = y + 2
+        let secret = LinearCombination::from_variable(y.get_variable())
+            + (
+                vesta::Base::from(2),
+                TestConstraintSystem::<vesta::Base>::one(),
+            );
+        let bindings = Bindings::new()
+            .bind(2, &x)
+            .bind_array(&symbols, "main.secret", [secret])
+            .unwrap();
+        let options = SynthesisOptions::new().bindings(bindings);
+        let signals =
+            synthesize_with(&mut cs, r1cs.clone(), Some(witness.clone()), options).unwrap();
+        assert!(cs.is_satisfied());
+        assert_eq!(signals.outputs.len(), 1);
+        assert_eq!(
+            signals.outputs[0].num.get_value(),
+            Some(vesta::Base::from(42))
+        );
+        // x, y and the output, but neither the public input nor the secret.
+        assert_eq!(cs.aux().len(), 3);
+        assert_eq!(signals.iter().count(), 1);
+
+        // A bound output is constrained to be equal to its binding.
+        for (out, satisfied) in [(42, true), (41, false)] {
+            let mut cs = TestConstraintSystem::<vesta::Base>::new();
+            let out =
+                AllocatedNum::alloc(cs.namespace(|| "out"), || Ok(vesta::Base::from(out))).unwrap();
+            let bindings = Bindings::new()
+                .bind_name(&symbols, "main.out", out)
+                .unwrap();
+            let options = SynthesisOptions::new().bindings(bindings);
+            synthesize_with(&mut cs, r1cs.clone(), Some(witness.clone()), options).unwrap();
+            assert_eq!(cs.is_satisfied(), satisfied);
+        }
+
+        // A bound public input stays public, and is constrained to be equal to its binding.
+        for (input, satisfied) in [(6, true), (5, false)] {
+            let mut cs = TestConstraintSystem::<vesta::Base>::new();
+            let input = AllocatedNum::alloc(cs.namespace(|| "in"), || Ok(vesta::Base::from(input)))
+                .unwrap();
+            let bindings = Bindings::new().bind(2, &input);
+            let options = SynthesisOptions::new()
+                .public_signals(PublicSignals::All)
+                .bindings(bindings);
+            let signals =
+                synthesize_with(&mut cs, r1cs.clone(), Some(witness.clone()), options).unwrap();
+            assert_eq!(cs.is_satisfied(), satisfied);
+            // The constant one, the output and the input.
+            assert_eq!(cs.num_inputs(), 3);
+            assert_eq!(signals.public_inputs.len(), 1);
+            assert!(signals.public_inputs[0].public);
+        }
+
+        assert_eq!(
+            Bindings::new()
+                .bind_name(&symbols, "main.missing", &x)
+                .unwrap_err(),
+            SignalError::NotFound {
+                name: "main.missing".into()
+            }
+        );
+        assert_eq!(
+            Bindings::new()
+                .bind_array(&symbols, "main.secret", [&x, &y])
+                .unwrap_err(),
+            SignalError::LengthMismatch {
+                name: "main.secret".into(),
+                expected: 1,
+                actual: 2
+            }
         );
     }
 
-    Ok(output)
+    #[test]
+    fn test_symbols() {
+        use crate::r1cs::{Symbol, SymbolTable};
+        use bellpepper_core::test_cs::TestConstraintSystem;
+
+        // t = in * secret, out = t, with one output, one public input, one private input and one
+        // intermediate signal.
+        let r1cs = R1CS {
+            num_pub_in: 1,
+            num_pub_out: 1,
+            num_prv_in: 1,
+            num_inputs: 3,
+            num_aux: 2,
+            num_variables: 5,
+            constraints: vec![
+                (
+                    vec![(2, vesta::Base::ONE)],
+                    vec![(3, vesta::Base::ONE)],
+                    vec![(4, vesta::Base::ONE)],
+                ),
+                (
+                    vec![(4, vesta::Base::ONE)],
+                    vec![(0, vesta::Base::ONE)],
+                    vec![(1, vesta::Base::ONE)],
+                ),
+            ],
+        };
+        let symbols = SymbolTable {
+            symbols: [
+                ("main.out", Some(1)),
+                ("main.in", Some(2)),
+                ("main.secret", Some(3)),
+                ("main.mul.out", Some(4)),
+                ("main.mul.a", Some(2)),
+                ("main.mul.unused", None),
+            ]
+            .into_iter()
+            .enumerate()
+            .map(|(i, (name, wire))| Symbol {
+                label: i + 1,
+                wire,
+                component: 0,
+                name: name.to_string(),
+            })
+            .collect(),
+        };
+        let witness = [1, 42, 6, 7, 42].map(vesta::Base::from).to_vec();
+
+        let mut cs = TestConstraintSystem::<vesta::Base>::new();
+        let options = SynthesisOptions::new().symbols(&symbols);
+        let signals =
+            synthesize_with(&mut cs, r1cs.clone(), Some(witness.clone()), options).unwrap();
+        assert!(cs.is_satisfied());
+
+        let group = |handles: &[SignalHandle<vesta::Base>]| {
+            handles
+                .iter()
+                .map(|handle| (handle.wire, handle.name.clone().unwrap()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(group(&signals.outputs), [(1, "main.out".to_string())]);
+        assert_eq!(group(&signals.public_inputs), [(2, "main.in".to_string())]);
+        assert_eq!(
+            group(&signals.private_inputs),
+            [(3, "main.secret".to_string())]
+        );
+        assert_eq!(group(&signals.aux), [(4, "main.mul.out".to_string())]);
+        assert_eq!(
+            signals.get("main.secret").unwrap().num.get_value(),
+            Some(vesta::Base::from(7))
+        );
+        // Aliases resolve to the signal sharing their wire.
+        let alias = signals.get("main.mul.a").unwrap();
+        assert_eq!(alias.wire, 2);
+        assert_eq!(alias.name.as_deref(), Some("main.in"));
+        assert!(signals.get("main.mul.unused").is_none());
+        assert!(signals.get("main.missing").is_none());
+        assert_eq!(signals.iter().count(), 4);
+
+        // Variables and constraints are named after the signals.
+        assert_eq!(cs.get("main.secret/num"), vesta::Base::from(7));
+        let mut cs = TestConstraintSystem::<vesta::Base>::new();
+        let mut wrong = witness.clone();
+        wrong[1] = vesta::Base::from(41);
+        let options = SynthesisOptions::new().symbols(&symbols);
+        synthesize_with(&mut cs, r1cs.clone(), Some(wrong), options).unwrap();
+        assert_eq!(
+            cs.which_is_unsatisfied(),
+            Some("constraint 1: main.mul.out, main.out")
+        );
+
+        // Signals that are not in the symbol table keep the default names.
+        let mut partial = symbols.clone();
+        partial
+            .symbols
+            .retain(|symbol| symbol.name != "main.mul.out");
+        let mut wrong = witness.clone();
+        wrong[4] = vesta::Base::from(41);
+        let mut cs = TestConstraintSystem::<vesta::Base>::new();
+        let options = SynthesisOptions::new().symbols(&partial);
+        synthesize_with(&mut cs, r1cs.clone(), Some(wrong), options).unwrap();
+        assert_eq!(
+            cs.which_is_unsatisfied(),
+            Some("constraint 0: main.in, main.secret, aux_1")
+        );
+        assert_eq!(cs.get("aux_1/num"), vesta::Base::from(41));
+
+        let mut cs = TestConstraintSystem::<vesta::Base>::new();
+        let signals =
+            synthesize_with(&mut cs, r1cs, Some(witness), SynthesisOptions::new()).unwrap();
+        assert!(signals.iter().all(|handle| handle.name.is_none()));
+        assert!(signals.get("main.out").is_none());
+        assert_eq!(
+            signals.outputs[0].num.get_value(),
+            Some(vesta::Base::from(42))
+        );
+    }
+
+    #[test]
+    fn test_constraint_name() {
+        let names = (1..=6).map(|i| format!("main.in[{i}]")).collect::<Vec<_>>();
+        let names = (1..=6)
+            .map(|i| (i, names[i - 1].as_str()))
+            .collect::<BTreeMap<_, _>>();
+        let labels = Labels {
+            names: &names,
+            num_inputs: 4,
+        };
+        let lc = |wires: &[usize]| {
+            wires
+                .iter()
+                .map(|&wire| (wire, vesta::Base::ONE))
+                .collect::<Vec<_>>()
+        };
+
+        let constraint = (lc(&[0, 1]), lc(&[2]), lc(&[1, 3]));
+        assert_eq!(
+            constraint_name(2, &constraint, &labels),
+            "constraint 2: main.in[1], main.in[2], main.in[3]"
+        );
+        let constraint = (lc(&[]), lc(&[]), lc(&[6, 5, 4, 3, 2, 1]));
+        assert_eq!(
+            constraint_name(0, &constraint, &labels),
+            "constraint 0: main.in[6], main.in[5], main.in[4], main.in[3] and 2 more"
+        );
+        let constraint = (lc(&[7]), lc(&[]), lc(&[1]));
+        assert_eq!(
+            constraint_name(1, &constraint, &labels),
+            "constraint 1: aux_3, main.in[1]"
+        );
+    }
 }
@@ -5,15 +5,31 @@
 //!
 //! This module provides structures to work with various files generated by Circom.
 
-use std::{path::Path, sync::Mutex};
+#[cfg(feature = "async")]
+use std::sync::Arc;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    num::NonZeroUsize,
+    path::Path,
+    thread::available_parallelism,
+};
 
 use anyhow::Result;
+use bellpepper_core::{num::AllocatedNum, LinearCombination};
 use ff::PrimeField;
 use serde::{Deserialize, Serialize};
 
 use crate::error::CircomConfigError::{LoadR1CSError, WitnessCalculatorInstantiationError};
 use crate::error::ReaderError::FilenameError;
-use crate::{reader::load_r1cs, witness::WitnessCalculator};
+use crate::error::SignalError::{self, LengthMismatch, NotFound, Removed, WireOutOfRange};
+#[cfg(feature = "async")]
+use crate::error::WitnessError;
+use crate::{
+    reader::load_r1cs,
+    witness::{Circom, DefaultEngine, WitnessCalculatorPool},
+};
+#[cfg(feature = "async")]
+use ff::PrimeFieldBits;
 
 /// Represents a Circom circuit with constraints and an optional witness.
 ///
@@ -30,11 +46,15 @@ pub(crate) struct CircomCircuit<F: PrimeField> {
 ///
 /// This includes the number of public inputs and outputs, total number of inputs, auxiliary inputs,
 /// variables, and the [`Constraint`] themselves.
+///
+/// Private inputs are part of the auxiliary inputs, as the first `num_prv_in` of them. R1CS files in
+/// JSON format without a `nPrvInputs` field count no private input.
 #[allow(dead_code)]
 #[derive(Clone, Debug)]
 pub struct R1CS<F: PrimeField> {
     pub num_pub_in: usize,
     pub num_pub_out: usize,
+    pub num_prv_in: usize,
     pub num_inputs: usize,
     pub num_aux: usize,
     pub num_variables: usize,
@@ -57,6 +77,522 @@ impl<F: PrimeField> CircomInput<F> {
     }
 }
 
+/// A signal of a circuit, as listed in its symbol table.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Symbol {
+    /// Label of the signal, before constraint simplification.
+    pub label: usize,
+    /// Index of the signal in the witness, or `None` if the signal was removed by the compiler.
+    pub wire: Option<usize>,
+    /// Index of the component the signal belongs to.
+    pub component: usize,
+    /// Fully qualified name of the signal, such as `main.hasher.out[5]`.
+    pub name: String,
+}
+
+/// Symbol table of a circuit, as generated by circom in the `.sym` file.
+#[derive(Clone, Debug, Default)]
+pub struct SymbolTable {
+    pub symbols: Vec<Symbol>,
+}
+
+impl SymbolTable {
+    /// Returns the names of the signals of the main component, without their `main.` prefix nor
+    /// array indices, in the order they appear in the table.
+    pub fn main_signals(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = vec![];
+        for symbol in &self.symbols {
+            let Some(name) = symbol.name.strip_prefix("main.") else {
+                continue;
+            };
+            let name = name.split('[').next().unwrap_or(name);
+            if !name.contains('.') && !names.contains(&name) {
+                names.push(name);
+            }
+        }
+        names
+    }
+
+    /// Returns the wire of the signal with the given fully qualified name, such as
+    /// `main.hasher.out[5]`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the signal is not in the symbol table, or if it was removed by constraint
+    /// simplification.
+    pub fn wire(&self, name: &str) -> Result<usize, SignalError> {
+        let symbol = self
+            .symbols
+            .iter()
+            .find(|symbol| symbol.name == name)
+            .ok_or_else(|| NotFound {
+                name: name.to_string(),
+            })?;
+        symbol_wire(symbol)
+    }
+
+    /// Returns the wires of the elements of the array signal with the given fully qualified name,
+    /// such as `main.hasher.out`, in the order of their indices. Multidimensional arrays are flattened
+    /// in row-major order, as circom does with inputs.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no element of the array is in the symbol table, or if one of them was
+    /// removed by constraint simplification.
+    pub fn array_wires(&self, name: &str) -> Result<Vec<usize>, SignalError> {
+        let mut elements = self
+            .symbols
+            .iter()
+            .filter_map(|symbol| {
+                let suffix = symbol.name.strip_prefix(name)?;
+                array_indices(suffix).map(|indices| (indices, symbol))
+            })
+            .collect::<Vec<_>>();
+        if elements.is_empty() {
+            return Err(NotFound {
+                name: name.to_string(),
+            });
+        }
+
+        elements.sort_by(|(a, _), (b, _)| a.cmp(b));
+        elements
+            .into_iter()
+            .map(|(_, symbol)| symbol_wire(symbol))
+            .collect()
+    }
+}
+
+/// Returns the wire of a signal, or an error if the signal was removed by constraint simplification.
+fn symbol_wire(symbol: &Symbol) -> Result<usize, SignalError> {
+    symbol.wire.ok_or_else(|| Removed {
+        name: symbol.name.clone(),
+    })
+}
+
+/// Which public signals of a circuit [`crate::synthesize_with`] allocates as public inputs of the
+/// constraint system, rather than as private variables.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum PublicSignals {
+    /// Every signal is allocated as a private variable, as [`crate::synthesize`] does.
+    #[default]
+    None,
+    /// Every output and public input signal is allocated as a public input.
+    All,
+    /// Only the public signals at the given wires are allocated as public inputs. Public signals
+    /// are at wires `1..num_inputs`, outputs first, and other wires are ignored.
+    Only(BTreeSet<usize>),
+}
+
+impl PublicSignals {
+    /// Returns whether the public signal at `wire` is allocated as a public input.
+    pub(crate) fn is_public(&self, wire: usize) -> bool {
+        match self {
+            Self::None => false,
+            Self::All => true,
+            Self::Only(wires) => wires.contains(&wire),
+        }
+    }
+}
+
+/// A signal of a circuit, as allocated by [`crate::synthesize_with`].
+#[derive(Clone, Debug)]
+pub struct SignalHandle<F: PrimeField> {
+    /// The wire of the signal.
+    pub wire: usize,
+    /// The fully qualified name of the signal, such as `main.out`, if a symbol table was provided.
+    /// Signals sharing a wire take the name listed first in the symbol table.
+    pub name: Option<String>,
+    /// Whether the signal was allocated as a public input of the constraint system.
+    pub public: bool,
+    pub num: AllocatedNum<F>,
+}
+
+/// The signals of a circuit, as allocated by [`crate::synthesize_with`], grouped by kind in the
+/// order of their wires.
+///
+/// Signals bound to variables of the enclosing constraint system have no handle, except for
+/// outputs, since they get no variable of their own.
+#[derive(Clone, Debug)]
+pub struct CircomSynthesized<F: PrimeField> {
+    pub outputs: Vec<SignalHandle<F>>,
+    pub public_inputs: Vec<SignalHandle<F>>,
+    pub private_inputs: Vec<SignalHandle<F>>,
+    /// The intermediate signals of the circuit, which are neither outputs nor inputs.
+    pub aux: Vec<SignalHandle<F>>,
+    /// The wire of each signal of the symbol table, by name, including the signals sharing a wire
+    /// with a signal listed before them.
+    pub(crate) wires: BTreeMap<String, usize>,
+}
+
+impl<F: PrimeField> CircomSynthesized<F> {
+    /// Iterates over all the signals of the circuit, in the order of their wires.
+    pub fn iter(&self) -> impl Iterator<Item = &SignalHandle<F>> + '_ {
+        self.outputs
+            .iter()
+            .chain(&self.public_inputs)
+            .chain(&self.private_inputs)
+            .chain(&self.aux)
+    }
+
+    /// Returns the signal with the given fully qualified name, such as `main.out`, or `None` if no
+    /// signal has that name, if it was removed by constraint simplification, or if no symbol table
+    /// was provided.
+    ///
+    /// Signals are looked up by wire, so that a signal sharing its wire with another one, such as the
+    /// input of a subcomponent connected to an input of the main component, returns the handle of
+    /// that wire, named after the signal listed first.
+    pub fn get(&self, name: &str) -> Option<&SignalHandle<F>> {
+        let wire = *self.wires.get(name)?;
+        self.iter().find(|signal| signal.wire == wire)
+    }
+}
+
+/// A variable of an enclosing constraint system, or a linear combination of such variables, to use for
+/// a signal of a circuit in [`crate::synthesize_with`].
+#[derive(Clone, Debug)]
+pub struct Binding<F: PrimeField>(pub LinearCombination<F>);
+
+impl<F: PrimeField> From<LinearCombination<F>> for Binding<F> {
+    fn from(lc: LinearCombination<F>) -> Self {
+        Self(lc)
+    }
+}
+
+impl<F: PrimeField> From<&AllocatedNum<F>> for Binding<F> {
+    fn from(num: &AllocatedNum<F>) -> Self {
+        Self(LinearCombination::from_variable(num.get_variable()))
+    }
+}
+
+impl<F: PrimeField> From<AllocatedNum<F>> for Binding<F> {
+    fn from(num: AllocatedNum<F>) -> Self {
+        Self::from(&num)
+    }
+}
+
+/// Signals of a circuit bound to variables of an enclosing constraint system, which
+/// [`crate::synthesize_with`] reuses instead of allocating fresh variables for them.
+///
+/// Signals are bound by wire, or by name through the symbol table of the circuit. Binding a signal
+/// twice keeps the last binding.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use bellpepper_core::{num::AllocatedNum, test_cs::TestConstraintSystem, ConstraintSystem};
+/// # use circom_scotia::{reader::load_sym, r1cs::Bindings};
+/// # use ff::Field;
+/// # use pasta_curves::vesta::Base as Fr;
+/// let mut cs = TestConstraintSystem::<Fr>::new();
+/// let x = AllocatedNum::alloc(cs.namespace(|| "x"), || Ok(Fr::ONE)).unwrap();
+/// let limbs = (0..4)
+///     .map(|i| AllocatedNum::alloc(cs.namespace(|| format!("limb {i}")), || Ok(Fr::ZERO)))
+///     .collect::<Result<Vec<_>, _>>()
+///     .unwrap();
+///
+/// let symbols = load_sym("circuit.sym").unwrap();
+/// let bindings = Bindings::new()
+///     .bind_name(&symbols, "main.x", &x)
+///     .unwrap()
+///     .bind_array(&symbols, "main.limbs", &limbs)
+///     .unwrap();
+/// ```
+#[derive(Clone, Debug)]
+pub struct Bindings<F: PrimeField> {
+    wires: BTreeMap<usize, LinearCombination<F>>,
+}
+
+impl<F: PrimeField> Default for Bindings<F> {
+    fn default() -> Self {
+        Self {
+            wires: BTreeMap::new(),
+        }
+    }
+}
+
+impl<F: PrimeField> Bindings<F> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Binds the signal at `wire`.
+    pub fn bind(mut self, wire: usize, binding: impl Into<Binding<F>>) -> Self {
+        self.wires.insert(wire, binding.into().0);
+        self
+    }
+
+    /// Binds the signal with the given fully qualified name, such as `main.x`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the signal is not in the symbol table, or if it was removed by constraint
+    /// simplification.
+    pub fn bind_name(
+        self,
+        symbols: &SymbolTable,
+        name: &str,
+        binding: impl Into<Binding<F>>,
+    ) -> Result<Self, SignalError> {
+        let wire = symbols.wire(name)?;
+        Ok(self.bind(wire, binding))
+    }
+
+    /// Binds the elements of the array signal with the given fully qualified name, such as
+    /// `main.limbs`, in the order of their indices.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no element of the array is in the symbol table, if one of them was removed
+    /// by constraint simplification, or if the array and `bindings` have different lengths.
+    pub fn bind_array<B: Into<Binding<F>>>(
+        mut self,
+        symbols: &SymbolTable,
+        name: &str,
+        bindings: impl IntoIterator<Item = B>,
+    ) -> Result<Self, SignalError> {
+        let wires = symbols.array_wires(name)?;
+        let bindings = bindings.into_iter().collect::<Vec<_>>();
+        if wires.len() != bindings.len() {
+            return Err(LengthMismatch {
+                name: name.to_string(),
+                expected: wires.len(),
+                actual: bindings.len(),
+            });
+        }
+
+        for (wire, binding) in wires.into_iter().zip(bindings) {
+            self = self.bind(wire, binding);
+        }
+        Ok(self)
+    }
+
+    /// Removes the binding of the signal at `wire`, if any.
+    pub(crate) fn take(&mut self, wire: usize) -> Option<LinearCombination<F>> {
+        self.wires.remove(&wire)
+    }
+}
+
+/// How [`crate::synthesize_with`] allocates the signals of a circuit: which public signals become
+/// public inputs of the constraint system, which signals reuse variables of an enclosing constraint
+/// system, and the symbol table naming them.
+///
+/// The default options allocate every signal as a private variable named `public_{i}` or `aux_{i}`,
+/// as [`crate::synthesize`] does.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use bellpepper_core::{num::AllocatedNum, test_cs::TestConstraintSystem, ConstraintSystem};
+/// # use circom_scotia::{reader::load_sym, r1cs::{Bindings, CircomConfig, PublicSignals, SynthesisOptions}, synthesize_with};
+/// # use pasta_curves::vesta::Base as Fr;
+/// let cfg: CircomConfig<Fr> = CircomConfig::new("circuit.wasm", "circuit.r1cs").unwrap();
+/// let symbols = load_sym("circuit.sym").unwrap();
+/// let mut cs = TestConstraintSystem::<Fr>::new();
+/// let x = AllocatedNum::alloc(cs.namespace(|| "x"), || Ok(Fr::from(3))).unwrap();
+/// let options = SynthesisOptions::new()
+///     .public_signals(PublicSignals::All)
+///     .bindings(Bindings::new().bind_name(&symbols, "main.x", &x).unwrap())
+///     .symbols(&symbols);
+/// let signals = synthesize_with(&mut cs, cfg.r1cs.clone(), None, options).unwrap();
+/// let hash = signals.get("main.hasher.out[0]").unwrap();
+/// ```
+#[derive(Clone, Debug)]
+pub struct SynthesisOptions<'a, F: PrimeField> {
+    pub(crate) public_signals: PublicSignals,
+    pub(crate) bindings: Bindings<F>,
+    pub(crate) symbols: Option<&'a SymbolTable>,
+}
+
+impl<F: PrimeField> Default for SynthesisOptions<'_, F> {
+    fn default() -> Self {
+        Self {
+            public_signals: PublicSignals::None,
+            bindings: Bindings::new(),
+            symbols: None,
+        }
+    }
+}
+
+impl<'a, F: PrimeField> SynthesisOptions<'a, F> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allocates the selected public signals as public inputs of the constraint system. Public
+    /// signals bound by [`SynthesisOptions::bindings`] are only allocated for outputs.
+    pub fn public_signals(mut self, public_signals: PublicSignals) -> Self {
+        self.public_signals = public_signals;
+        self
+    }
+
+    /// Reuses variables of the enclosing constraint system for the bound signals.
+    ///
+    /// Bound signals get no variable of their own: the constraints of the circuit refer to their
+    /// bindings directly, so that connecting a circom gadget to the rest of a circuit costs no
+    /// equality constraint. Bound outputs, and bound inputs selected by
+    /// [`SynthesisOptions::public_signals`], are still allocated, so that public signals stay
+    /// public, and constrained to be equal to their bindings. Values of bound signals in the witness are ignored, so that they must agree with
+    /// their bindings for the constraint system to be satisfied.
+    ///
+    /// Bindings of wire 0, which holds the constant one, and of wires past the last signal of the
+    /// circuit are ignored.
+    pub fn bindings(mut self, bindings: Bindings<F>) -> Self {
+        self.bindings = bindings;
+        self
+    }
+
+    /// Names the signals after the symbol table of the circuit, as loaded from its `.sym` file.
+    ///
+    /// Variables are allocated in namespaces named after their signals, such as `main.sha.out[3]`,
+    /// and constraints are named after the signals they involve, so that
+    /// [`bellpepper_core::test_cs::TestConstraintSystem::which_is_unsatisfied`] points at them.
+    /// Signals that are not in the symbol table keep the `public_{i}` and `aux_{i}` namespaces.
+    pub fn symbols(mut self, symbols: &'a SymbolTable) -> Self {
+        self.symbols = Some(symbols);
+        self
+    }
+}
+
+/// A witness of a circuit, along with the symbol table of the circuit, to look up the values of its
+/// signals by name.
+///
+/// Signals are mapped to witness wires as listed in the symbol table: signals removed by constraint
+/// simplification have no value in the witness.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use circom_scotia::{reader::load_sym, r1cs::Witness};
+/// # use pasta_curves::vesta::Base as Fr;
+/// # let values: Vec<Fr> = vec![];
+/// let witness = Witness::new(values, load_sym("circuit.sym").unwrap());
+/// let out = witness.get("main.out").unwrap();
+/// let hash = witness.get_array("main.hasher.out").unwrap();
+/// for (name, value) in witness.iter() {
+///     println!("{name} = {value:?}");
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct Witness<F: PrimeField> {
+    values: Vec<F>,
+    symbols: SymbolTable,
+    /// Index in the symbol table of each signal, by name.
+    names: BTreeMap<String, usize>,
+}
+
+impl<F: PrimeField> Witness<F> {
+    /// Wraps the values of a witness, as calculated for a circuit, with the symbol table of the
+    /// circuit.
+    pub fn new(values: Vec<F>, symbols: SymbolTable) -> Self {
+        let names = symbols
+            .symbols
+            .iter()
+            .enumerate()
+            .map(|(i, symbol)| (symbol.name.clone(), i))
+            .collect();
+        Self {
+            values,
+            symbols,
+            names,
+        }
+    }
+
+    /// Returns the values of the witness, indexed by wire.
+    pub fn values(&self) -> &[F] {
+        &self.values
+    }
+
+    /// Returns the values of the witness, indexed by wire.
+    pub fn into_values(self) -> Vec<F> {
+        self.values
+    }
+
+    /// Returns the symbol table of the circuit.
+    pub fn symbols(&self) -> &SymbolTable {
+        &self.symbols
+    }
+
+    /// Returns the value of the signal with the given fully qualified name, such as
+    /// `main.hasher.out[5]`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the signal is not in the symbol table, or if it is not part of the witness.
+    pub fn get(&self, name: &str) -> Result<F, SignalError> {
+        let symbol = self
+            .names
+            .get(name)
+            .map(|&i| &self.symbols.symbols[i])
+            .ok_or_else(|| NotFound {
+                name: name.to_string(),
+            })?;
+        self.value(symbol)
+    }
+
+    /// Returns the values of the array signal with the given fully qualified name, such as
+    /// `main.hasher.out`, in the order of their indices. Multidimensional arrays are flattened in
+    /// row-major order, as circom does with inputs.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no element of the array is in the symbol table, or if one of them is not
+    /// part of the witness.
+    pub fn get_array(&self, name: &str) -> Result<Vec<F>, SignalError> {
+        // Elements are named `name[i]`, or `name[i][j]` and so on, and sort between `name[` and
+        // `name\`, as `\` follows `[`.
+        let mut elements = self
+            .names
+            .range(format!("{name}[")..format!("{name}\\"))
+            .filter_map(|(element, &i)| {
+                array_indices(&element[name.len()..]).map(|indices| (indices, i))
+            })
+            .collect::<Vec<_>>();
+        if elements.is_empty() {
+            return Err(NotFound {
+                name: name.to_string(),
+            });
+        }
+
+        elements.sort();
+        elements
+            .into_iter()
+            .map(|(_, i)| self.value(&self.symbols.symbols[i]))
+            .collect()
+    }
+
+    /// Iterates over the signals that are part of the witness, in the order of the symbol table,
+    /// along with their values.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, F)> + '_ {
+        self.symbols
+            .symbols
+            .iter()
+            .filter_map(|symbol| Some((symbol.name.as_str(), self.value(symbol).ok()?)))
+    }
+
+    fn value(&self, symbol: &Symbol) -> Result<F, SignalError> {
+        let wire = symbol_wire(symbol)?;
+        self.values
+            .get(wire)
+            .copied()
+            .ok_or_else(|| WireOutOfRange {
+                name: symbol.name.clone(),
+                wire,
+                len: self.values.len(),
+            })
+    }
+}
+
+/// Parses the indices of an array element, such as `[2][5]`, or returns `None` if `suffix` is not
+/// made of indices only, such as `[2].out` which names a signal of a subcomponent.
+fn array_indices(suffix: &str) -> Option<Vec<usize>> {
+    suffix
+        .strip_prefix('[')?
+        .strip_suffix(']')?
+        .split("][")
+        .map(|index| index.parse().ok())
+        .collect()
+}
+
 /// Type alias for representing a single constraint in an R1CS.
 ///
 /// A constraint is represented as a tuple of three vectors. Each vector contains pairs
@@ -67,11 +603,12 @@ pub(crate) type Constraint<F> = (Vec<(usize, F)>, Vec<(usize, F)>, Vec<(usize, F
 /// Configuration for Circom circuit processing.
 ///
 /// This structure holds the configuration necessary to handle R1CS files and witness calculation.
-/// It includes the [`R1CS`] structure, a mutex-protected [`WitnessCalculator`], and a flag for sanity checks.
+/// It includes the [`R1CS`] structure, a [`WitnessCalculatorPool`] so that witnesses can be calculated
+/// concurrently, running on the engine `C`, and a flag for sanity checks.
 #[derive(Debug)]
-pub struct CircomConfig<F: PrimeField> {
+pub struct CircomConfig<F: PrimeField, C: Circom = DefaultEngine> {
     pub r1cs: R1CS<F>,
-    pub wtns: Mutex<WitnessCalculator>,
+    pub wtns: WitnessCalculatorPool<C>,
     pub sanity_check: bool,
 }
 
@@ -82,17 +619,48 @@ impl<F: PrimeField> CircomConfig<F> {
     /// `r1cs`: Path to the R1CS file representing the circuit constraints.
     ///
     /// Returns a result containing the new [`CircomConfig`] instance or an error if the files
-    /// cannot be loaded or parsed correctly.
+    /// cannot be loaded or parsed correctly, or if either of them was compiled for a prime other
+    /// than the modulus of `F`.
+    ///
+    /// The witness calculator pool holds up to one calculator per available CPU.
     pub fn new(wtns: impl AsRef<Path>, r1cs: impl AsRef<Path>) -> Result<Self> {
+        let pool_size = available_parallelism().map_or(1, NonZeroUsize::get);
+        Self::with_pool_size(wtns, r1cs, pool_size)
+    }
+
+    /// Create a new [`CircomConfig`] instance whose witness calculator pool holds up to
+    /// `pool_size` calculators.
+    ///
+    /// See [`CircomConfig::new`].
+    pub fn with_pool_size(
+        wtns: impl AsRef<Path>,
+        r1cs: impl AsRef<Path>,
+        pool_size: usize,
+    ) -> Result<Self> {
+        Self::load(wtns, r1cs, pool_size)
+    }
+}
+
+impl<F: PrimeField, C: Circom> CircomConfig<F, C> {
+    /// Create a new [`CircomConfig`] instance whose witness calculators run on the engine `C`, in a
+    /// pool of up to `pool_size` calculators.
+    ///
+    /// See [`CircomConfig::new`].
+    pub fn load(wtns: impl AsRef<Path>, r1cs: impl AsRef<Path>, pool_size: usize) -> Result<Self> {
         let path_wtns_string = wtns.as_ref().to_str().ok_or(FilenameError)?.to_string();
         let path_r1cs_string = r1cs.as_ref().to_str().ok_or(FilenameError)?.to_string();
 
-        let wtns = Mutex::new(WitnessCalculator::new(wtns).map_err(|err| {
-            WitnessCalculatorInstantiationError {
+        // The witness calculator must have been compiled for the prime of `F`. The r1cs prime is
+        // checked against `F` while reading its header.
+        let wtns = WitnessCalculatorPool::load(wtns, pool_size)
+            .and_then(|wtns| {
+                wtns.get()?.check_prime::<F>()?;
+                Ok(wtns)
+            })
+            .map_err(|err| WitnessCalculatorInstantiationError {
                 path: path_wtns_string,
-                source: err.into(),
-            }
-        })?);
+                source: err,
+            })?;
         let r1cs = load_r1cs(r1cs).map_err(|err| LoadR1CSError {
             path: path_r1cs_string,
             source: err.into(),
@@ -104,3 +672,208 @@ impl<F: PrimeField> CircomConfig<F> {
         })
     }
 }
+
+#[cfg(feature = "async")]
+impl<F: PrimeFieldBits, C: Circom + Send + 'static> CircomConfig<F, C>
+where
+    C::Store: Send,
+    C::Module: Send + Sync,
+{
+    /// Calculates a witness like [`crate::calculate_witness`], without blocking the async executor.
+    ///
+    /// The task waits asynchronously for a calculator of the pool to be free, then calculates the
+    /// witness on the blocking thread pool of the tokio runtime, so that as many witnesses as the
+    /// pool has calculators are calculated concurrently.
+    ///
+    /// Dropping the returned future cancels the calculation if it has not started yet. A calculation
+    /// that has started runs to completion on its blocking thread, and its witness is discarded.
+    ///
+    /// # Errors
+    ///
+    /// See [`crate::calculate_witness`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of a tokio runtime, or if the calculation panicked.
+    pub async fn calculate_witness_async(
+        self: &Arc<Self>,
+        input: Vec<CircomInput<F>>,
+        sanity_check: bool,
+    ) -> Result<Vec<F>, WitnessError> {
+        let permit = self.wtns.acquire().await;
+        let cfg = Arc::clone(self);
+        let task = AbortOnDrop(tokio::task::spawn_blocking(move || {
+            let _permit = permit;
+            crate::calculate_witness(&cfg, input, sanity_check)
+        }));
+
+        task.await
+            .unwrap_or_else(|err| std::panic::resume_unwind(err.into_panic()))
+    }
+}
+
+/// Aborts a blocking task when dropped, which cancels it if it has not started yet.
+#[cfg(feature = "async")]
+struct AbortOnDrop<T>(tokio::task::JoinHandle<T>);
+
+#[cfg(feature = "async")]
+impl<T> std::future::Future for AbortOnDrop<T> {
+    type Output = Result<T, tokio::task::JoinError>;
+
+    fn poll(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Self::Output> {
+        std::pin::Pin::new(&mut self.0).poll(cx)
+    }
+}
+
+#[cfg(feature = "async")]
+impl<T> Drop for AbortOnDrop<T> {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pasta_curves::vesta;
+
+    use super::*;
+
+    fn symbol(label: usize, wire: Option<usize>, name: &str) -> Symbol {
+        Symbol {
+            label,
+            wire,
+            component: 0,
+            name: name.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_witness() {
+        // Labels and wires differ once signals are removed by constraint simplification.
+        let symbols = SymbolTable {
+            symbols: vec![
+                symbol(1, Some(1), "main.out"),
+                symbol(2, Some(2), "main.in[0][0]"),
+                symbol(3, Some(3), "main.in[0][1]"),
+                symbol(4, None, "main.in[1][0]"),
+                symbol(5, Some(4), "main.in[1][1]"),
+                symbol(6, Some(6), "main.bits[10]"),
+                symbol(7, Some(5), "main.bits[2]"),
+                symbol(8, Some(7), "main.bits[2].out"),
+                symbol(9, Some(42), "main.extra"),
+            ],
+        };
+        let values = (0..8).map(vesta::Base::from).collect::<Vec<_>>();
+        let witness = Witness::new(values, symbols);
+
+        assert_eq!(witness.get("main.out"), Ok(vesta::Base::from(1)));
+        assert_eq!(witness.get("main.in[1][1]"), Ok(vesta::Base::from(4)));
+        assert_eq!(
+            witness.get("main.in[1][0]"),
+            Err(Removed {
+                name: "main.in[1][0]".into()
+            })
+        );
+        assert_eq!(
+            witness.get("main.in"),
+            Err(NotFound {
+                name: "main.in".into()
+            })
+        );
+        assert_eq!(
+            witness.get("main.extra"),
+            Err(WireOutOfRange {
+                name: "main.extra".into(),
+                wire: 42,
+                len: 8
+            })
+        );
+
+        // Indices are sorted numerically, and subcomponent signals are not array elements.
+        assert_eq!(
+            witness.get_array("main.bits"),
+            Ok(vec![vesta::Base::from(5), vesta::Base::from(6)])
+        );
+        assert!(matches!(witness.get_array("main.in"), Err(Removed { .. })));
+        assert!(matches!(
+            witness.get_array("main.out"),
+            Err(NotFound { .. })
+        ));
+
+        let names = witness.iter().map(|(name, _)| name).collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [
+                "main.out",
+                "main.in[0][0]",
+                "main.in[0][1]",
+                "main.in[1][1]",
+                "main.bits[10]",
+                "main.bits[2]",
+                "main.bits[2].out"
+            ]
+        );
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_calculate_witness_async() {
+        use std::time::Duration;
+
+        use ff::Field;
+
+        let cfg = Arc::new(CircomConfig {
+            r1cs: R1CS {
+                num_pub_in: 0,
+                num_pub_out: 0,
+                num_prv_in: 0,
+                num_inputs: 0,
+                num_aux: 0,
+                num_variables: 0,
+                constraints: vec![],
+            },
+            wtns: WitnessCalculatorPool::new("circom/sha256/circom_sha256.wasm", 2).unwrap(),
+            sanity_check: true,
+        });
+        let inputs = |i: u64| {
+            vec![CircomInput::new(
+                "arg_in".into(),
+                vec![vesta::Base::ZERO, vesta::Base::from(i)],
+            )]
+        };
+
+        let tasks = (0..4)
+            .map(|i| {
+                let cfg = Arc::clone(&cfg);
+                tokio::spawn(async move { cfg.calculate_witness_async(inputs(i), true).await })
+            })
+            .collect::<Vec<_>>();
+        for (i, task) in (0..4).zip(tasks) {
+            let witness = task.await.unwrap().unwrap();
+            assert_eq!(
+                witness,
+                crate::calculate_witness(&cfg, inputs(i), true).unwrap()
+            );
+        }
+        assert!(cfg.wtns.instantiated() <= 2);
+
+        // A calculation waiting for a calculator is cancelled when dropped.
+        let permits = (cfg.wtns.acquire().await, cfg.wtns.acquire().await);
+        let pending = tokio::time::timeout(
+            Duration::from_millis(100),
+            cfg.calculate_witness_async(inputs(0), true),
+        );
+        assert!(pending.await.is_err());
+        drop(permits);
+
+        let err = cfg
+            .calculate_witness_async(vec![CircomInput::new("foo".into(), vec![])], true)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, WitnessError::WitnessCalculationError { .. }));
+        assert!(cfg.wtns.instantiated() <= 2);
+    }
+}
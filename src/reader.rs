@@ -8,25 +8,27 @@
 //! constraints.
 
 use anyhow::{anyhow, Context, Error, Result};
-use ff::PrimeField;
+use ff::{PrimeField, PrimeFieldBits};
 use ruint::aliases::U256;
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 use std::collections::HashMap;
 use std::fs::{File, OpenOptions};
-use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::io::{BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
 use std::path::Path;
 
 use crate::error::ReaderError::{
     self, FieldByteSizeError, FilenameError, NonMatchingPrime, OpenFileError, R1CSHeaderError,
-    R1CSVersionNotSupported, ReadBytesError, ReadFieldError, ReadIntegerError, ReadWitnessError,
-    SectionCountError, SectionLengthError, SectionNotFound, SectionTypeError, SeekError, WireError,
-    WitnessHeaderError, WitnessVersionNotSupported,
+    R1CSVersionNotSupported, ReadBytesError, ReadFieldError, ReadIntegerError, ReadSymbolsError,
+    ReadWitnessError, SectionCountError, SectionLengthError, SectionNotFound, SectionTypeError,
+    SeekError, WireError, WitnessHeaderError, WitnessVersionNotSupported,
 };
-use byteorder::{LittleEndian, ReadBytesExt};
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 
 use crate::r1cs::Constraint;
 use crate::r1cs::R1CS;
+use crate::r1cs::{CircomInput, Symbol, SymbolTable};
+use crate::util::{ff_as_limbs, limbs_as_decimal};
 
 /// Represents R1CS (Rank-1 Constraint System) data extracted from a JSON file.
 ///
@@ -39,6 +41,8 @@ pub(crate) struct CircuitJson {
     num_inputs: usize,
     #[serde(rename = "nOutputs")]
     num_outputs: usize,
+    #[serde(rename = "nPrvInputs", default)]
+    num_prv_inputs: usize,
     #[serde(rename = "nVars")]
     num_variables: usize,
 }
@@ -85,7 +89,7 @@ pub(crate) fn load_witness_from_file<F: PrimeField>(
 ///
 /// This function reads the witness data from a binary file specified by the `filename`.
 /// It leverages a [`BufReader`] for efficient reading and returns a vector of field elements.
-fn load_witness_from_bin_file<F: PrimeField>(
+pub(crate) fn load_witness_from_bin_file<F: PrimeField>(
     filename: impl AsRef<Path>,
 ) -> std::result::Result<Vec<F>, ReaderError> {
     let path_string = filename.as_ref().to_str().ok_or(FilenameError)?.to_string();
@@ -109,7 +113,7 @@ fn load_witness_from_bin_file<F: PrimeField>(
 /// This function reads the witness data from a binary reader and returns a vector of
 /// field elements. It handles the binary format of the witness data, ensuring correct
 /// parsing and conversion into field elements.
-fn load_witness_from_bin_reader<F: PrimeField, R: Read>(
+pub(crate) fn load_witness_from_bin_reader<F: PrimeField, R: Read>(
     mut reader: R,
 ) -> std::result::Result<Vec<F>, ReaderError> {
     let mut wtns_header = [0u8; 4];
@@ -158,6 +162,14 @@ fn load_witness_from_bin_reader<F: PrimeField, R: Read>(
     reader
         .read_exact(&mut prime)
         .map_err(|err| ReadBytesError { source: err.into() })?;
+    let prime = U256::from_le_slice(&prime);
+    let expected_prime = U256::from_str_radix(&F::MODULUS[2..], 16).ok();
+    if expected_prime != Some(prime) {
+        return Err(NonMatchingPrime {
+            expected: expected_prime.map_or_else(|| F::MODULUS.to_string(), |p| format!("{p:#x}")),
+            value: format!("{prime:#x}"),
+        });
+    }
 
     // Read the second section.
     let witness_len = reader
@@ -226,6 +238,141 @@ fn load_witness_from_json<F: PrimeField, R: Read>(reader: R) -> Result<Vec<F>> {
         .collect()
 }
 
+/// Loads circuit inputs from a JSON reader.
+///
+/// The JSON object maps the name of each input signal to its value, in the format read by the
+/// witness generators of circom: numbers, decimal or `0x` prefixed hexadecimal strings, possibly
+/// negative, and arrays of these, which are flattened. Numbers out of the 64-bit range are rejected,
+/// as JSON parsers read them as floats: such values must be written as strings.
+pub(crate) fn load_inputs_from_json<F: PrimeField, R: Read>(
+    reader: R,
+) -> Result<Vec<CircomInput<F>>> {
+    let inputs: serde_json::Map<String, serde_json::Value> =
+        serde_json::from_reader(reader).context("Failed to parse JSON")?;
+    inputs
+        .into_iter()
+        .map(|(name, value)| {
+            let mut values = vec![];
+            flatten_input_value(&value, &mut values)
+                .with_context(|| format!("Failed to parse input '{}'", name))?;
+            Ok(CircomInput::new(name, values))
+        })
+        .collect()
+}
+
+/// Appends the field elements of a JSON input value to `values`, flattening arrays.
+fn flatten_input_value<F: PrimeField>(
+    value: &serde_json::Value,
+    values: &mut Vec<F>,
+) -> Result<()> {
+    match value {
+        serde_json::Value::Array(array) => array
+            .iter()
+            .try_for_each(|value| flatten_input_value(value, values)),
+        serde_json::Value::Number(number) if number.is_f64() => Err(anyhow!(
+            "Unexpected number {}: only integers in the 64-bit range can be written as numbers, write other integers as strings",
+            number
+        )),
+        serde_json::Value::Number(number) => {
+            values.push(parse_input_value(&number.to_string())?);
+            Ok(())
+        }
+        serde_json::Value::String(string) => {
+            values.push(parse_input_value(string)?);
+            Ok(())
+        }
+        value => Err(anyhow!("Unexpected value: {}", value)),
+    }
+}
+
+/// Parses a field element from a possibly negative decimal, or `0x` prefixed hexadecimal, integer.
+fn parse_input_value<F: PrimeField>(value: &str) -> Result<F> {
+    let (negative, digits) = match value.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, value),
+    };
+    let (radix, digits) = match digits.strip_prefix("0x") {
+        Some(digits) => (16, digits),
+        None => (10, digits),
+    };
+    if digits.is_empty() {
+        return Err(anyhow!("Failed to parse field element: '{}'", value));
+    }
+
+    let mut res = F::ZERO;
+    for c in digits.chars() {
+        let digit = c
+            .to_digit(radix)
+            .with_context(|| format!("Failed to parse field element: '{}'", value))?;
+        res = res * F::from(u64::from(radix)) + F::from(u64::from(digit));
+    }
+
+    Ok(if negative { -res } else { res })
+}
+
+/// Writes Circom inputs as a JSON object, mapping the name of each input signal to its values in decimal.
+pub(crate) fn inputs_as_json<F: PrimeFieldBits>(inputs: &[CircomInput<F>]) -> String {
+    let n32 = (F::NUM_BITS as usize + 31) / 32;
+    let inputs = inputs
+        .iter()
+        .map(|input| {
+            let values = input
+                .value
+                .iter()
+                .map(|&value| serde_json::Value::String(limbs_as_decimal(&ff_as_limbs(value, n32))))
+                .collect();
+            (input.name.clone(), serde_json::Value::Array(values))
+        })
+        .collect::<serde_json::Map<_, _>>();
+
+    serde_json::Value::Object(inputs).to_string()
+}
+
+/// Writes witness data to a binary file, in the `.wtns` format read by snarkjs.
+pub(crate) fn write_witness_to_bin_file<F: PrimeField>(
+    filename: impl AsRef<Path>,
+    witness: &[F],
+) -> std::io::Result<()> {
+    let mut writer = BufWriter::new(File::create(filename)?);
+    write_witness_to_bin_writer(&mut writer, witness)?;
+    writer.flush()
+}
+
+/// Writes witness data to a binary writer, in the `.wtns` format read by snarkjs.
+///
+/// The field elements are written in their little endian representation, along with the modulus of
+/// their field.
+pub(crate) fn write_witness_to_bin_writer<F: PrimeField, W: Write>(
+    mut writer: W,
+    witness: &[F],
+) -> std::io::Result<()> {
+    let field_size = F::ZERO.to_repr().as_ref().len();
+    let mut prime = U256::from_str_radix(&F::MODULUS[2..], 16)
+        .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidInput, err))?
+        .to_le_bytes_vec();
+    prime.resize(field_size, 0);
+
+    writer.write_all(b"wtns")?;
+    writer.write_u32::<LittleEndian>(2)?;
+    writer.write_u32::<LittleEndian>(2)?;
+
+    // Header section: the field size, the prime and the witness length.
+    writer.write_u32::<LittleEndian>(1)?;
+    writer.write_u64::<LittleEndian>(4 + field_size as u64 + 4)?;
+    writer.write_u32::<LittleEndian>(field_size as u32)?;
+    writer.write_all(&prime)?;
+    writer.write_u32::<LittleEndian>(witness.len() as u32)?;
+
+    // Witness section.
+    writer.write_u32::<LittleEndian>(2)?;
+    writer.write_u64::<LittleEndian>((field_size * witness.len()) as u64)?;
+    for value in witness {
+        writer.write_all(value.to_repr().as_ref())?;
+    }
+
+    Ok(())
+}
+
 /// Loads an R1CS (Rank-1 Constraint System) from a binary file.
 ///
 /// Reads an R1CS file in binary format, returning an `R1CS` structure that represents
@@ -300,12 +447,12 @@ fn read_header<R: Read>(
     let expected_prime =
         U256::from_str_radix(&expected_prime[2..], 16).map_err(|_err| NonMatchingPrime {
             expected: expected_prime.to_string(),
-            value: prime.to_string(),
+            value: format!("{prime:#x}"),
         })?;
     if prime != expected_prime {
         return Err(NonMatchingPrime {
-            expected: expected_prime.to_string(),
-            value: prime.to_string(),
+            expected: format!("{expected_prime:#x}"),
+            value: format!("{prime:#x}"),
         });
     }
 
@@ -522,6 +669,7 @@ fn load_r1cs_from_bin<F: PrimeField, R: Read + Seek>(reader: R) -> Result<R1CS<F
     let file = from_reader(reader)?;
     let num_pub_in = file.header.n_pub_in as usize;
     let num_pub_out = file.header.n_pub_out as usize;
+    let num_prv_in = file.header.n_prv_in as usize;
     let num_inputs = (1 + file.header.n_pub_in + file.header.n_pub_out) as usize;
     let num_variables = file.header.n_wires as usize;
     let num_aux = num_variables - num_inputs;
@@ -530,6 +678,7 @@ fn load_r1cs_from_bin<F: PrimeField, R: Read + Seek>(reader: R) -> Result<R1CS<F
         num_aux,
         num_pub_in,
         num_pub_out,
+        num_prv_in,
         num_inputs,
         num_variables,
         constraints: file.constraints,
@@ -609,9 +758,273 @@ fn load_r1cs_from_json<F: PrimeField, R: Read>(reader: R) -> Result<R1CS<F>> {
     Ok(R1CS {
         num_pub_in,
         num_pub_out,
+        num_prv_in: circuit_json.num_prv_inputs,
         num_inputs,
         num_aux,
         num_variables: circuit_json.num_variables,
         constraints,
     })
 }
+
+/// Loads the [`SymbolTable`] of a circuit from the `.sym` file generated by circom.
+pub fn load_sym(filename: impl AsRef<Path>) -> Result<SymbolTable, ReaderError> {
+    let path_string = filename.as_ref().to_str().ok_or(FilenameError)?.to_string();
+    let reader = OpenOptions::new()
+        .read(true)
+        .open(&filename)
+        .map_err(|err| OpenFileError {
+            filename: path_string.clone(),
+            source: err.into(),
+        })?;
+    load_sym_from_reader(BufReader::new(reader)).map_err(|err| ReadSymbolsError {
+        filename: path_string,
+        source: err.into(),
+    })
+}
+
+/// Loads a [`SymbolTable`] from a reader.
+///
+/// Each line of a `.sym` file reads `<label>,<wire>,<component>,<name>`, where the wire is `-1`
+/// for signals removed by the compiler.
+fn load_sym_from_reader<R: BufRead>(reader: R) -> Result<SymbolTable> {
+    let symbols = reader
+        .lines()
+        .filter(|line| !matches!(line, Ok(line) if line.trim().is_empty()))
+        .map(|line| {
+            let line = line?;
+            let mut fields = line.trim().splitn(4, ',');
+            let mut next = || {
+                fields
+                    .next()
+                    .with_context(|| format!("Malformed symbol: '{}'", line))
+            };
+            let label = next()?.parse()?;
+            let wire: i64 = next()?.parse()?;
+            let component = next()?.parse()?;
+            let name = next()?.to_string();
+            Ok(Symbol {
+                label,
+                wire: usize::try_from(wire).ok(),
+                component,
+                name,
+            })
+        })
+        .collect::<Result<_>>()?;
+
+    Ok(SymbolTable { symbols })
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::io::Cursor;
+
+    use ff::Field;
+    use pasta_curves::{pallas, vesta};
+
+    use super::*;
+
+    /// Builds a minimal binary r1cs file with a single wire and no constraints.
+    fn r1cs_bytes<F: PrimeField>() -> Vec<u8> {
+        r1cs_bytes_with::<F>(1, 0, 0, &[])
+    }
+
+    /// Builds a binary r1cs file with the given numbers of wires, public outputs and private inputs,
+    /// and the given constraints.
+    pub(crate) fn r1cs_bytes_with<F: PrimeField>(
+        n_wires: u32,
+        n_pub_out: u32,
+        n_prv_in: u32,
+        constraints: &[Constraint<F>],
+    ) -> Vec<u8> {
+        let prime = U256::from_str_radix(&F::MODULUS[2..], 16).unwrap();
+
+        let mut bytes = b"r1cs".to_vec();
+        bytes.write_u32::<LittleEndian>(1).unwrap();
+        bytes.write_u32::<LittleEndian>(3).unwrap();
+
+        // Header section.
+        bytes.write_u32::<LittleEndian>(1).unwrap();
+        bytes.write_u64::<LittleEndian>(64).unwrap();
+        bytes.write_u32::<LittleEndian>(32).unwrap();
+        bytes.extend_from_slice(&prime.to_le_bytes::<32>());
+        bytes.write_u32::<LittleEndian>(n_wires).unwrap();
+        bytes.write_u32::<LittleEndian>(n_pub_out).unwrap();
+        bytes.write_u32::<LittleEndian>(0).unwrap();
+        bytes.write_u32::<LittleEndian>(n_prv_in).unwrap();
+        bytes.write_u64::<LittleEndian>(n_wires as u64).unwrap();
+        bytes
+            .write_u32::<LittleEndian>(constraints.len() as u32)
+            .unwrap();
+
+        // Constraints section.
+        let mut section = vec![];
+        for (a, b, c) in constraints {
+            for lc in [a, b, c] {
+                section.write_u32::<LittleEndian>(lc.len() as u32).unwrap();
+                for (wire, coeff) in lc {
+                    section.write_u32::<LittleEndian>(*wire as u32).unwrap();
+                    section.extend_from_slice(coeff.to_repr().as_ref());
+                }
+            }
+        }
+        bytes.write_u32::<LittleEndian>(2).unwrap();
+        bytes
+            .write_u64::<LittleEndian>(section.len() as u64)
+            .unwrap();
+        bytes.extend_from_slice(&section);
+
+        // Wire to label section.
+        bytes.write_u32::<LittleEndian>(3).unwrap();
+        bytes.write_u64::<LittleEndian>(8 * n_wires as u64).unwrap();
+        for wire in 0..n_wires as u64 {
+            bytes.write_u64::<LittleEndian>(wire).unwrap();
+        }
+
+        bytes
+    }
+
+    /// Builds a binary wtns file holding the given witness.
+    pub(crate) fn wtns_bytes<F: PrimeField>(witness: &[F]) -> Vec<u8> {
+        let prime = U256::from_str_radix(&F::MODULUS[2..], 16).unwrap();
+
+        let mut bytes = b"wtns".to_vec();
+        bytes.write_u32::<LittleEndian>(2).unwrap();
+        bytes.write_u32::<LittleEndian>(2).unwrap();
+
+        // Header section.
+        bytes.write_u32::<LittleEndian>(1).unwrap();
+        bytes.write_u64::<LittleEndian>(40).unwrap();
+        bytes.write_u32::<LittleEndian>(32).unwrap();
+        bytes.extend_from_slice(&prime.to_le_bytes::<32>());
+        bytes
+            .write_u32::<LittleEndian>(witness.len() as u32)
+            .unwrap();
+
+        // Witness section.
+        bytes.write_u32::<LittleEndian>(2).unwrap();
+        bytes
+            .write_u64::<LittleEndian>(32 * witness.len() as u64)
+            .unwrap();
+        for value in witness {
+            bytes.extend_from_slice(value.to_repr().as_ref());
+        }
+
+        bytes
+    }
+
+    #[test]
+    fn test_load_witness_from_bin() {
+        let witness = [vesta::Base::ONE, vesta::Base::from(42), -vesta::Base::ONE];
+        let bytes = wtns_bytes(&witness);
+
+        let loaded = load_witness_from_bin_reader::<vesta::Base, _>(Cursor::new(&bytes)).unwrap();
+        assert_eq!(loaded, witness);
+
+        let err = load_witness_from_bin_reader::<pallas::Base, _>(Cursor::new(&bytes)).unwrap_err();
+        let ReaderError::NonMatchingPrime { expected, value } = err else {
+            panic!("unexpected error: {err}");
+        };
+        assert_eq!(expected, pallas::Base::MODULUS);
+        assert_eq!(value, vesta::Base::MODULUS);
+    }
+
+    #[test]
+    fn test_write_witness_to_bin() {
+        let witness = [vesta::Base::ONE, vesta::Base::from(42), -vesta::Base::ONE];
+        let mut bytes = vec![];
+        write_witness_to_bin_writer(&mut bytes, &witness).unwrap();
+        assert_eq!(bytes, wtns_bytes(&witness));
+    }
+
+    #[test]
+    fn test_inputs_as_json() {
+        let inputs = vec![CircomInput::new(
+            "in".into(),
+            vec![vesta::Base::ZERO, -vesta::Base::ONE],
+        )];
+        assert_eq!(
+            inputs_as_json(&inputs),
+            format!(
+                r#"{{"in":["0","{}"]}}"#,
+                crate::util::ff_as_u256(-vesta::Base::ONE)
+            )
+        );
+    }
+
+    #[test]
+    fn test_load_inputs_from_json() {
+        let json = r#"{"a": 3, "b": ["-1", "0x10", [7, "007"]]}"#;
+        let inputs = load_inputs_from_json::<vesta::Base, _>(json.as_bytes()).unwrap();
+        assert_eq!(inputs.len(), 2);
+        assert_eq!(inputs[0].name, "a");
+        assert_eq!(inputs[0].value, [vesta::Base::from(3)]);
+        assert_eq!(inputs[1].name, "b");
+        assert_eq!(
+            inputs[1].value,
+            [
+                -vesta::Base::ONE,
+                vesta::Base::from(16),
+                vesta::Base::from(7),
+                vesta::Base::from(7)
+            ]
+        );
+
+        for json in [
+            r#"{"a": "0x"}"#,
+            r#"{"a": "1.5"}"#,
+            r#"{"a": 1.5}"#,
+            r#"{"a": 123456789012345678901234567890}"#,
+            r#"{"a": {"b": 1}}"#,
+            "[1]",
+        ] {
+            assert!(load_inputs_from_json::<vesta::Base, _>(json.as_bytes()).is_err());
+        }
+    }
+
+    #[test]
+    fn test_load_r1cs_from_bin() {
+        let r1cs =
+            load_r1cs_from_bin::<vesta::Base, _>(Cursor::new(r1cs_bytes::<vesta::Base>())).unwrap();
+        assert_eq!(r1cs.num_variables, 1);
+        assert!(r1cs.constraints.is_empty());
+    }
+
+    #[test]
+    fn test_load_r1cs_from_bin_inputs() {
+        let bytes = r1cs_bytes_with::<vesta::Base>(5, 1, 2, &[]);
+        let r1cs = load_r1cs_from_bin::<vesta::Base, _>(Cursor::new(bytes)).unwrap();
+        assert_eq!(r1cs.num_pub_out, 1);
+        assert_eq!(r1cs.num_prv_in, 2);
+        assert_eq!(r1cs.num_inputs, 2);
+        assert_eq!(r1cs.num_aux, 3);
+    }
+
+    #[test]
+    fn test_load_sym() {
+        let sym = "1,1,0,main.out[0]\n2,-1,0,main.out[1]\n3,2,0,main.in\n4,3,1,main.hasher.out\n";
+        let table = load_sym_from_reader(Cursor::new(sym)).unwrap();
+        assert_eq!(table.symbols.len(), 4);
+        assert_eq!(
+            table.symbols[1],
+            Symbol {
+                label: 2,
+                wire: None,
+                component: 0,
+                name: "main.out[1]".into(),
+            }
+        );
+        assert_eq!(table.main_signals(), vec!["out", "in"]);
+
+        assert!(load_sym_from_reader(Cursor::new("1,1,main.out")).is_err());
+    }
+
+    #[test]
+    fn test_load_r1cs_non_matching_prime() {
+        let err = load_r1cs_from_bin::<pallas::Base, _>(Cursor::new(r1cs_bytes::<vesta::Base>()))
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(ReaderError::NonMatchingPrime { .. })
+        ));
+    }
+}
@@ -15,10 +15,11 @@ pub fn limbs_as_u256(limbs: [u32; 8]) -> U256 {
     U256::from_limbs(limbs)
 }
 
-/// Converts a field element into an little endian array of `[u32; 8]` limbs
-pub fn ff_as_limbs<F: PrimeFieldBits>(f: F) -> [u32; 8] {
-    let mut limbs = [0u32; 8];
-    for (i, bit) in f.to_le_bits().iter().enumerate() {
+/// Converts a field element into a little endian vector of `n32` [`u32`] limbs. Bits that do not fit
+/// in `n32` limbs are dropped
+pub fn ff_as_limbs<F: PrimeFieldBits>(f: F, n32: usize) -> Vec<u32> {
+    let mut limbs = vec![0u32; n32];
+    for (i, bit) in f.to_le_bits().iter().enumerate().take(n32 * 32) {
         if *bit {
             let limb_index = i / 32;
             let bit_index = i % 32;
@@ -28,8 +29,8 @@ pub fn ff_as_limbs<F: PrimeFieldBits>(f: F) -> [u32; 8] {
     limbs
 }
 
-/// Converts a little endian array of `[u32; 8]` limbs into a field element
-pub fn limbs_as_ff<F: PrimeField>(limbs: [u32; 8]) -> F {
+/// Converts a little endian slice of [`u32`] limbs into a field element
+pub fn limbs_as_ff<F: PrimeField>(limbs: &[u32]) -> F {
     let mut res = F::ZERO;
     let radix = F::from(0x0001_0000_0000_u64);
     for &val in limbs.iter().rev() {
@@ -38,15 +39,76 @@ pub fn limbs_as_ff<F: PrimeField>(limbs: [u32; 8]) -> F {
     res
 }
 
-/// Converts a [`U256`] into a field element. We assume the field's size matches 256 bits
+/// Strips the most significant zero limbs of a little endian slice of [`u32`] limbs
+pub fn trim_limbs(limbs: &[u32]) -> &[u32] {
+    let len = limbs
+        .iter()
+        .rposition(|&limb| limb != 0)
+        .map_or(0, |i| i + 1);
+    &limbs[..len]
+}
+
+/// Formats a little endian slice of [`u32`] limbs as a `0x` prefixed hexadecimal string
+pub fn limbs_as_hex(limbs: &[u32]) -> String {
+    limbs
+        .iter()
+        .rev()
+        .fold(String::from("0x"), |hex, limb| hex + &format!("{limb:08x}"))
+}
+
+/// Formats a little endian slice of [`u32`] limbs as a decimal string
+pub fn limbs_as_decimal(limbs: &[u32]) -> String {
+    // Divide by 10^9 until nothing is left, collecting the least significant digits first.
+    let mut limbs = trim_limbs(limbs).to_vec();
+    let mut chunks = vec![];
+    while !limbs.is_empty() {
+        let mut rem = 0u64;
+        for limb in limbs.iter_mut().rev() {
+            let cur = (rem << 32) | u64::from(*limb);
+            *limb = (cur / 1_000_000_000) as u32;
+            rem = cur % 1_000_000_000;
+        }
+        chunks.push(rem);
+        limbs.truncate(trim_limbs(&limbs).len());
+    }
+
+    match chunks.split_last() {
+        None => String::from("0"),
+        Some((first, rest)) => rest
+            .iter()
+            .rev()
+            .fold(first.to_string(), |dec, chunk| dec + &format!("{chunk:09}")),
+    }
+}
+
+/// Returns the modulus of the field as little endian [`u32`] limbs, without the most significant
+/// zero limbs. Returns `None` if [`PrimeField::MODULUS`] is not an hexadecimal string
+pub fn modulus_as_limbs<F: PrimeField>() -> Option<Vec<u32>> {
+    let limbs = F::MODULUS
+        .trim_start_matches("0x")
+        .as_bytes()
+        .rchunks(8)
+        .map(|chunk| {
+            std::str::from_utf8(chunk)
+                .ok()
+                .and_then(|chunk| u32::from_str_radix(chunk, 16).ok())
+        })
+        .collect::<Option<Vec<_>>>()?;
+    Some(trim_limbs(&limbs).to_vec())
+}
+
+/// Converts a [`U256`] into a field element
+#[allow(unused)]
 pub fn u256_as_ff<F: PrimeField>(uint: U256) -> F {
-    limbs_as_ff(u256_as_limbs(uint))
+    limbs_as_ff(&u256_as_limbs(uint))
 }
 
 #[allow(unused)]
 /// Converts a field element into a [`U256`]. We assume the field's size matches 256 bits
 pub fn ff_as_u256<F: PrimeFieldBits>(f: F) -> U256 {
-    limbs_as_u256(ff_as_limbs(f))
+    let mut limbs = [0u32; 8];
+    limbs.copy_from_slice(&ff_as_limbs(f, 8));
+    limbs_as_u256(limbs)
 }
 
 #[cfg(test)]
@@ -76,12 +138,44 @@ mod tests {
 
         for _ in 0..100 {
             let f = pallas::Scalar::random(&mut rng);
-            let limbs = ff_as_limbs(f);
-            let other_f = limbs_as_ff(limbs);
+            let limbs = ff_as_limbs(f, 8);
+            let other_f = limbs_as_ff(&limbs);
             assert_eq!(f, other_f)
         }
     }
 
+    #[test]
+    fn test_modulus_as_limbs() {
+        let modulus = modulus_as_limbs::<pallas::Scalar>().unwrap();
+        assert_eq!(modulus.len(), 8);
+        assert_eq!(limbs_as_hex(&modulus), pallas::Scalar::MODULUS);
+        assert_eq!(
+            limbs_as_ff::<pallas::Scalar>(&modulus),
+            pallas::Scalar::ZERO
+        );
+    }
+
+    #[test]
+    fn test_limbs_as_decimal() {
+        assert_eq!(limbs_as_decimal(&[]), "0");
+        assert_eq!(limbs_as_decimal(&[42, 0]), "42");
+        assert_eq!(limbs_as_decimal(&[0, 1]), "4294967296");
+        assert_eq!(
+            limbs_as_decimal(&u256_as_limbs(U256::MAX)),
+            U256::MAX.to_string()
+        );
+    }
+
+    #[test]
+    fn test_trim_limbs() {
+        assert_eq!(trim_limbs(&[1, 2, 0, 0]), &[1, 2]);
+        assert_eq!(trim_limbs(&[0, 0]), &[] as &[u32]);
+        assert_eq!(
+            limbs_as_hex(&[0x0000_0001, 0xffff_ffff]),
+            "0xffffffff00000001"
+        );
+    }
+
     #[test]
     fn test_u256_ff_roundtrip() {
         let mut rng = rand::thread_rng();
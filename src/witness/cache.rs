@@ -0,0 +1,558 @@
+// Copyright (c) Lurk Lab
+// SPDX-License-Identifier: MIT
+//! # Witness cache module
+//!
+//! The `cache` module provides [`WitnessCache`], which stores the witnesses calculated for a circuit so that identical
+//! calculations, such as retries or repeated proofs of the same statement, are not run again.
+//!
+//! Witnesses are content-addressed: they are looked up by a [`WitnessKey`] hashing the WebAssembly module of the
+//! circuit, the field of the witness, the inputs of the calculation and whether it ran the sanity checks of the
+//! circuit. Inputs are canonicalized by sorting them by
+//! name, so that the order in which they are given does not matter. Witnesses are kept either in memory, evicting the
+//! least recently used ones, or on disk as `.wtns` files, evicting the oldest ones, within a limit on their total
+//! size.
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+use ff::{PrimeField, PrimeFieldBits};
+use log::warn;
+use sha2::{Digest, Sha256};
+
+use super::{Circom, Result, WitnessCalculator, WitnessCalculatorError::NonMatchingModule};
+use crate::r1cs::CircomInput;
+use crate::reader::{load_witness_from_bin_reader, write_witness_to_bin_writer};
+
+/// Extension of the files holding witnesses in an on-disk cache.
+const EXTENSION: &str = "wtns";
+
+/// The key of a witness in a [`WitnessCache`], hashing the module of the circuit, the field of the witness, the inputs
+/// of the calculation and whether it ran the sanity checks of the circuit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct WitnessKey([u8; 32]);
+
+impl fmt::Display for WitnessKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.iter().try_for_each(|byte| write!(f, "{byte:02x}"))
+    }
+}
+
+/// A cache of the witnesses calculated for a circuit.
+///
+/// The cache can be shared between threads. An on-disk cache can also be shared between processes, and between
+/// circuits since keys depend on the module of the circuit.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use circom_scotia::{r1cs::CircomInput, witness::{WitnessCache, WitnessCalculator}};
+/// # use ff::Field;
+/// # use pasta_curves::vesta::Base as Fr;
+/// let cache = WitnessCache::in_memory("circuit.wasm", 1 << 30).unwrap();
+/// let mut wtns = WitnessCalculator::new("circuit.wasm").unwrap();
+/// let inputs = vec![CircomInput::new("in".into(), vec![Fr::ZERO])];
+///
+/// // The second calculation is served from the cache.
+/// let witness = cache.calculate_witness(&mut wtns, inputs.clone(), true).unwrap();
+/// assert_eq!(cache.calculate_witness(&mut wtns, inputs, true).unwrap(), witness);
+/// ```
+#[derive(Debug)]
+pub struct WitnessCache {
+    /// Hash of the WebAssembly module of the circuit.
+    module: [u8; 32],
+    storage: Storage,
+}
+
+/// Where the witnesses of a [`WitnessCache`] are kept, as `.wtns` bytes.
+#[derive(Debug)]
+enum Storage {
+    Memory(Mutex<MemoryStorage>),
+    Disk {
+        dir: PathBuf,
+        max_bytes: u64,
+        /// Serializes evictions, so that concurrent insertions do not evict the same files.
+        lock: Mutex<()>,
+    },
+}
+
+/// Witnesses kept in memory, along with the order in which they were last used.
+#[derive(Debug)]
+struct MemoryStorage {
+    entries: HashMap<WitnessKey, (u64, Vec<u8>)>,
+    /// Keys of the entries by the tick at which they were last used.
+    recent: BTreeMap<u64, WitnessKey>,
+    tick: u64,
+    bytes: usize,
+    max_bytes: usize,
+}
+
+impl MemoryStorage {
+    /// Returns the bytes of the witness `key`, marking it as the most recently used one.
+    fn get(&mut self, key: &WitnessKey) -> Option<Vec<u8>> {
+        let tick = self.tick;
+        let (used, bytes) = self.entries.get_mut(key)?;
+        self.recent.remove(used);
+        self.recent.insert(tick, *key);
+        *used = tick;
+        self.tick += 1;
+        Some(bytes.clone())
+    }
+
+    /// Stores the bytes of the witness `key`, evicting the least recently used witnesses to stay within the size limit.
+    /// Witnesses larger than the limit are not stored.
+    fn insert(&mut self, key: WitnessKey, bytes: Vec<u8>) {
+        self.remove(&key);
+        if bytes.len() > self.max_bytes {
+            return;
+        }
+
+        while self.bytes + bytes.len() > self.max_bytes {
+            let Some((_, oldest)) = self.recent.pop_first() else {
+                break;
+            };
+            if let Some((_, evicted)) = self.entries.remove(&oldest) {
+                self.bytes -= evicted.len();
+            }
+        }
+
+        self.bytes += bytes.len();
+        self.recent.insert(self.tick, key);
+        self.entries.insert(key, (self.tick, bytes));
+        self.tick += 1;
+    }
+
+    fn remove(&mut self, key: &WitnessKey) {
+        if let Some((used, bytes)) = self.entries.remove(key) {
+            self.recent.remove(&used);
+            self.bytes -= bytes.len();
+        }
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+        self.recent.clear();
+        self.bytes = 0;
+    }
+}
+
+impl WitnessCache {
+    /// Constructs a [`WitnessCache`] keeping up to `max_bytes` of witnesses in memory, for the circuit compiled to the
+    /// given WebAssembly module. The least recently used witnesses are evicted first.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the WebAssembly module cannot be read.
+    pub fn in_memory(wasm: impl AsRef<Path>, max_bytes: usize) -> Result<Self> {
+        Ok(Self {
+            module: hash_module(wasm)?,
+            storage: Storage::Memory(Mutex::new(MemoryStorage {
+                entries: HashMap::new(),
+                recent: BTreeMap::new(),
+                tick: 0,
+                bytes: 0,
+                max_bytes,
+            })),
+        })
+    }
+
+    /// Constructs a [`WitnessCache`] keeping up to `max_bytes` of witnesses as `.wtns` files in `dir`, for the circuit
+    /// compiled to the given WebAssembly module. The oldest witnesses are evicted first.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the WebAssembly module cannot be read, or if `dir` cannot be created.
+    pub fn on_disk(
+        wasm: impl AsRef<Path>,
+        dir: impl Into<PathBuf>,
+        max_bytes: u64,
+    ) -> Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(Self {
+            module: hash_module(wasm)?,
+            storage: Storage::Disk {
+                dir,
+                max_bytes,
+                lock: Mutex::new(()),
+            },
+        })
+    }
+
+    /// Returns the key of the witness calculated for the given inputs, with or without the sanity checks of the circuit.
+    pub fn key<F: PrimeField>(&self, inputs: &[CircomInput<F>], sanity_check: bool) -> WitnessKey {
+        let mut inputs = inputs.iter().collect::<Vec<_>>();
+        inputs.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let mut hasher = Sha256::new();
+        hasher.update(self.module);
+        hasher.update(F::MODULUS.as_bytes());
+        hasher.update([u8::from(sanity_check)]);
+        for input in inputs {
+            hasher.update((input.name.len() as u64).to_le_bytes());
+            hasher.update(input.name.as_bytes());
+            hasher.update((input.value.len() as u64).to_le_bytes());
+            for value in &input.value {
+                hasher.update(value.to_repr());
+            }
+        }
+        WitnessKey(hasher.finalize().into())
+    }
+
+    /// Returns the witness stored under `key`, if any.
+    ///
+    /// Entries that cannot be read, or that do not hold elements of the field `F`, are treated as missing.
+    pub fn get<F: PrimeField>(&self, key: &WitnessKey) -> Option<Vec<F>> {
+        let bytes = match &self.storage {
+            Storage::Memory(memory) => lock(memory).get(key)?,
+            Storage::Disk { dir, .. } => match fs::read(entry_path(dir, key)) {
+                Ok(bytes) => bytes,
+                Err(err) if err.kind() == io::ErrorKind::NotFound => return None,
+                Err(err) => {
+                    warn!("could not read cached witness {key}: {err}");
+                    return None;
+                }
+            },
+        };
+
+        load_witness_from_bin_reader(bytes.as_slice())
+            .map_err(|err| warn!("could not read cached witness {key}: {err}"))
+            .ok()
+    }
+
+    /// Stores `witness` under `key`, evicting other witnesses to stay within the size limit of the cache.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the witness cannot be written to disk.
+    pub fn insert<F: PrimeField>(&self, key: WitnessKey, witness: &[F]) -> Result<()> {
+        let mut bytes = vec![];
+        write_witness_to_bin_writer(&mut bytes, witness)?;
+
+        match &self.storage {
+            Storage::Memory(memory) => lock(memory).insert(key, bytes),
+            Storage::Disk {
+                dir,
+                max_bytes,
+                lock: eviction,
+            } => {
+                if bytes.len() as u64 > *max_bytes {
+                    return Ok(());
+                }
+                let _eviction = lock(eviction);
+                evict(dir, *max_bytes - bytes.len() as u64)?;
+
+                // Entries are renamed into place, so that readers never see a partial witness.
+                let path = entry_path(dir, &key);
+                let partial = path.with_extension(format!("{EXTENSION}.{}", std::process::id()));
+                fs::write(&partial, bytes)?;
+                fs::rename(&partial, path)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Removes the witness stored under `key`, if any.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the witness cannot be removed from disk.
+    pub fn invalidate(&self, key: &WitnessKey) -> Result<()> {
+        match &self.storage {
+            Storage::Memory(memory) => lock(memory).remove(key),
+            Storage::Disk { dir, .. } => match fs::remove_file(entry_path(dir, key)) {
+                Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err.into()),
+                _ => {}
+            },
+        }
+
+        Ok(())
+    }
+
+    /// Removes all the witnesses of the cache. An on-disk cache removes every `.wtns` file of its directory, including
+    /// the witnesses of other circuits.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the witnesses cannot be removed from disk.
+    pub fn clear(&self) -> Result<()> {
+        match &self.storage {
+            Storage::Memory(memory) => lock(memory).clear(),
+            Storage::Disk {
+                dir,
+                lock: eviction,
+                ..
+            } => {
+                let _eviction = lock(eviction);
+                evict(dir, 0)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the witness cached for the given inputs, or calculates it with `wtns` and caches it.
+    ///
+    /// `wtns` must have been loaded from a file holding the module of the cache, with
+    /// [`WitnessCalculator::new`], [`WitnessCalculator::load`] or a [`super::WitnessCalculatorPool`]
+    /// loaded from a file, so that cached witnesses are only served for the circuit they were
+    /// calculated for. Witnesses calculated without the sanity checks of the circuit are only served
+    /// to calculations without them, while sanity-checked witnesses are served to both. Failed
+    /// calculations are not cached, and a witness that cannot be cached is still returned.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`NonMatchingModule`] if `wtns` does not run the module of
+    /// the cache, or was not loaded from a file, and an error if the witness is not cached and its
+    /// calculation fails, see [`WitnessCalculator::calculate_witness`].
+    pub fn calculate_witness<F: PrimeFieldBits>(
+        &self,
+        wtns: &mut WitnessCalculator<impl Circom>,
+        inputs: Vec<CircomInput<F>>,
+        sanity_check: bool,
+    ) -> Result<Vec<F>> {
+        if wtns.module_hash != Some(self.module) {
+            return Err(NonMatchingModule);
+        }
+
+        let key = self.key(&inputs, sanity_check);
+        let cached = match self.get(&key) {
+            None if !sanity_check => self.get(&self.key(&inputs, true)),
+            cached => cached,
+        };
+        if let Some(witness) = cached {
+            return Ok(witness);
+        }
+
+        let witness = wtns.calculate_witness(inputs, sanity_check)?;
+        if let Err(err) = self.insert(key, &witness) {
+            warn!("could not cache witness {key}: {err}");
+        }
+        Ok(witness)
+    }
+}
+
+/// Hashes the contents of a WebAssembly module.
+pub(super) fn hash_module(wasm: impl AsRef<Path>) -> Result<[u8; 32]> {
+    Ok(Sha256::digest(fs::read(wasm)?).into())
+}
+
+/// The memory storage only holds complete entries and counters, which remain consistent even if a thread panicked
+/// while holding the lock.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn entry_path(dir: &Path, key: &WitnessKey) -> PathBuf {
+    dir.join(format!("{key}.{EXTENSION}"))
+}
+
+/// Removes the oldest `.wtns` files of `dir` until they take up at most `max_bytes`.
+fn evict(dir: &Path, max_bytes: u64) -> io::Result<()> {
+    let mut entries = vec![];
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        if path
+            .extension()
+            .map_or(true, |extension| extension != EXTENSION)
+        {
+            continue;
+        }
+        // Entries may be removed concurrently by other processes sharing the directory.
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        entries.push((metadata.modified()?, metadata.len(), path));
+    }
+
+    let mut bytes = entries.iter().map(|(_, len, _)| len).sum::<u64>();
+    entries.sort();
+    for (_, len, path) in entries {
+        if bytes <= max_bytes {
+            break;
+        }
+        match fs::remove_file(path) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+            _ => bytes -= len,
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use ff::Field;
+    use pasta_curves::{pallas, vesta};
+
+    use super::*;
+    use crate::witness::WitnessCalculatorPool;
+
+    const SHA256: &str = "circom/sha256/circom_sha256.wasm";
+
+    fn inputs(a: u64, b: u64) -> Vec<CircomInput<vesta::Base>> {
+        vec![
+            CircomInput::new("a".into(), vec![vesta::Base::from(a)]),
+            CircomInput::new("b".into(), vec![vesta::Base::from(b), vesta::Base::ONE]),
+        ]
+    }
+
+    /// Size of the `.wtns` bytes of a witness of `len` elements.
+    fn wtns_size(len: usize) -> usize {
+        4 + 4 + 4 + (4 + 8 + 4 + 32 + 4) + (4 + 8 + 32 * len)
+    }
+
+    #[test]
+    fn test_key() {
+        let cache = WitnessCache::in_memory(SHA256, 0).unwrap();
+        let key = cache.key(&inputs(1, 2), true);
+
+        let mut reordered = inputs(1, 2);
+        reordered.reverse();
+        assert_eq!(cache.key(&reordered, true), key);
+        assert_ne!(cache.key(&inputs(2, 1), true), key);
+        assert_ne!(cache.key(&inputs(1, 2), false), key);
+
+        // Values moved from one input to another change the key.
+        let moved = vec![
+            CircomInput::new("a".into(), vec![vesta::Base::from(1), vesta::Base::from(2)]),
+            CircomInput::new("b".into(), vec![vesta::Base::ONE]),
+        ];
+        assert_ne!(cache.key(&moved, true), key);
+
+        let pallas_inputs = vec![
+            CircomInput::new("a".into(), vec![pallas::Base::from(1)]),
+            CircomInput::new("b".into(), vec![pallas::Base::from(2), pallas::Base::ONE]),
+        ];
+        assert_ne!(cache.key(&pallas_inputs, true), key);
+        assert_eq!(key.to_string().len(), 64);
+    }
+
+    #[test]
+    fn test_memory_cache() {
+        let witness = |len: u64| (0..len).map(vesta::Base::from).collect::<Vec<_>>();
+        let cache = WitnessCache::in_memory(SHA256, wtns_size(3) + wtns_size(2)).unwrap();
+        let [first, second, third] =
+            [(1, 1), (2, 2), (3, 3)].map(|(a, b)| cache.key(&inputs(a, b), true));
+
+        cache.insert(first, &witness(3)).unwrap();
+        cache.insert(second, &witness(2)).unwrap();
+        assert_eq!(cache.get(&first), Some(witness(3)));
+        assert_eq!(cache.get::<pallas::Base>(&first), None);
+
+        // The least recently used witness is evicted.
+        cache.insert(third, &witness(1)).unwrap();
+        assert_eq!(cache.get::<vesta::Base>(&second), None);
+        assert_eq!(cache.get(&first), Some(witness(3)));
+        assert_eq!(cache.get(&third), Some(witness(1)));
+
+        cache.invalidate(&first).unwrap();
+        assert_eq!(cache.get::<vesta::Base>(&first), None);
+        cache.clear().unwrap();
+        assert_eq!(cache.get::<vesta::Base>(&third), None);
+
+        // Witnesses larger than the cache are not stored.
+        cache.insert(first, &witness(12)).unwrap();
+        assert_eq!(cache.get::<vesta::Base>(&first), None);
+    }
+
+    #[test]
+    fn test_disk_cache() {
+        let dir = std::env::temp_dir().join(format!("circom-scotia-cache-{}", std::process::id()));
+        let cache = WitnessCache::on_disk(SHA256, &dir, 2 * wtns_size(29823) as u64).unwrap();
+        let mut wtns = WitnessCalculator::new(SHA256).unwrap();
+        let sha256_input = |a: u64| {
+            vec![CircomInput::new(
+                "arg_in".into(),
+                vec![vesta::Base::ZERO, vesta::Base::from(a)],
+            )]
+        };
+
+        let witness = cache
+            .calculate_witness(&mut wtns, sha256_input(0), true)
+            .unwrap();
+        let key = cache.key(&sha256_input(0), true);
+        assert!(entry_path(&dir, &key).exists());
+        assert_eq!(cache.get(&key), Some(witness.clone()));
+
+        // Calculators of other circuits are rejected, even for cached witnesses.
+        let mut other = WitnessCalculator::new("circom/mock/adder_goldilocks.wat").unwrap();
+        assert!(matches!(
+            cache.calculate_witness(&mut other, sha256_input(0), true),
+            Err(NonMatchingModule)
+        ));
+
+        // Cached witnesses are returned without calculating them.
+        let pool = WitnessCalculatorPool::new(SHA256, 1).unwrap();
+        assert_eq!(
+            cache
+                .calculate_witness(&mut pool.get().unwrap(), sha256_input(0), true)
+                .unwrap(),
+            witness
+        );
+
+        // The oldest witness is evicted.
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        cache
+            .calculate_witness(&mut wtns, sha256_input(1), true)
+            .unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        cache
+            .calculate_witness(&mut wtns, sha256_input(2), true)
+            .unwrap();
+        assert!(!entry_path(&dir, &key).exists());
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+
+        // A corrupted entry is a miss.
+        let key = cache.key(&sha256_input(1), true);
+        fs::write(entry_path(&dir, &key), b"wtns").unwrap();
+        assert_eq!(cache.get::<vesta::Base>(&key), None);
+
+        cache.invalidate(&key).unwrap();
+        assert!(!entry_path(&dir, &key).exists());
+        cache.invalidate(&key).unwrap();
+        cache.clear().unwrap();
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_sanity_check() {
+        let cache = WitnessCache::in_memory(SHA256, 1 << 24).unwrap();
+        let mut wtns = WitnessCalculator::new(SHA256).unwrap();
+        let inputs = || {
+            vec![CircomInput::new(
+                "arg_in".into(),
+                vec![vesta::Base::ZERO, vesta::Base::ONE],
+            )]
+        };
+
+        // A witness calculated without the sanity checks is not served to a calculation with them.
+        let unchecked = vec![vesta::Base::ZERO; 3];
+        cache
+            .insert(cache.key(&inputs(), false), &unchecked)
+            .unwrap();
+        let witness = cache.calculate_witness(&mut wtns, inputs(), true).unwrap();
+        assert_ne!(witness, unchecked);
+        assert_eq!(
+            cache.get(&cache.key(&inputs(), true)),
+            Some(witness.clone())
+        );
+        assert_eq!(
+            cache.calculate_witness(&mut wtns, inputs(), false).unwrap(),
+            unchecked
+        );
+
+        // A sanity-checked witness is served to a calculation without the checks.
+        cache.invalidate(&cache.key(&inputs(), false)).unwrap();
+        assert_eq!(
+            cache.calculate_witness(&mut wtns, inputs(), false).unwrap(),
+            witness
+        );
+    }
+}
@@ -6,96 +6,197 @@
 //! The `circom` module provides an interface for working with WebAssembly (WASM) instances, specifically tailored for
 //! Circom-based cryptographic computations. It includes functionality to interact with Circom-compiled WASM functions and
 //! manage the Circom computation environment.
+//!
+//! The [`Circom`] trait is independent of the engine running the WASM: each engine, wasmer in the `wasmer_calculator`
+//! module and wasmi in the `wasmi_calculator` module, implements it for its instances.
 
-use anyhow::Result;
-use wasmer::{AsStoreMut, Function, Instance, Value};
+use std::fmt;
+use std::path::Path;
 
-/// Represents a WebAssembly instance for Circom computations.
-#[derive(Clone, Debug)]
-pub struct Wasm(Instance);
+use ff::{PrimeField, PrimeFieldBits};
 
-/// Base trait for interacting with Circom WASM instances.
-pub trait Circom {
-    fn init(&self, store: &mut impl AsStoreMut, sanity_check: bool) -> Result<()>;
-    fn func(&self, name: &str) -> &Function;
-    fn get_ptr_witness_buffer(&self, store: &mut impl AsStoreMut) -> Result<u32>;
-    fn get_ptr_witness(&self, store: &mut impl AsStoreMut, w: u32) -> Result<u32>;
-    fn get_n_vars(&self, store: &mut impl AsStoreMut) -> Result<u32>;
-    fn get_signal_offset32(
-        &self,
-        store: &mut impl AsStoreMut,
-        p_sig_offset: u32,
-        component: u32,
-        hash_msb: u32,
-        hash_lsb: u32,
-    ) -> Result<()>;
-    fn set_signal(
-        &self,
-        store: &mut impl AsStoreMut,
-        c_idx: u32,
-        component: u32,
-        signal: u32,
-        p_val: u32,
-    ) -> Result<()>;
-    fn get_u32(&self, store: &mut impl AsStoreMut, name: &str) -> Result<u32>;
-    fn get_version(&self, store: &mut impl AsStoreMut) -> Result<u32>;
-    fn get_fr_len(&self, store: &mut impl AsStoreMut) -> Result<u32>;
-    fn get_ptr_raw_prime(&self, store: &mut impl AsStoreMut) -> Result<u32>;
-
-    fn get_field_num_len32(&self, store: &mut impl AsStoreMut) -> Result<u32>;
-    fn get_raw_prime(&self, store: &mut impl AsStoreMut) -> Result<()>;
-    fn read_shared_rw_memory(&self, store: &mut impl AsStoreMut, i: u32) -> Result<u32>;
-    fn write_shared_rw_memory(&self, store: &mut impl AsStoreMut, i: u32, v: u32) -> Result<()>;
-    fn set_input_signal(
-        &self,
-        store: &mut impl AsStoreMut,
-        hmsb: u32,
-        hlsb: u32,
-        pos: u32,
-    ) -> Result<()>;
-    fn get_witness(&self, store: &mut impl AsStoreMut, i: u32) -> Result<()>;
-    fn get_witness_size(&self, store: &mut impl AsStoreMut) -> Result<u32>;
+use super::{
+    fnv,
+    witness_calculator::Messages,
+    Result,
+    WitnessCalculatorError::{
+        InputLengthMismatch, MissingInputs, NonMatchingPrime, UnexpectedReturn, UnknownInput,
+        VersionNotSupported,
+    },
+};
+use crate::r1cs::{CircomInput, SymbolTable};
+use crate::util::{limbs_as_hex, modulus_as_limbs, trim_limbs};
+
+/// Functions exported by circom 1 witness generators that are used to calculate witnesses.
+const CIRCOM1_EXPORTS: &[&str] = &[
+    "init",
+    "getFrLen",
+    "getPRawPrime",
+    "getNVars",
+    "getPWitness",
+    "getSignalOffset32",
+    "setSignal",
+];
+
+/// Functions exported by circom 2 witness generators that are used to calculate witnesses.
+const CIRCOM2_EXPORTS: &[&str] = &[
+    "init",
+    "getFieldNumLen32",
+    "getRawPrime",
+    "readSharedRWMemory",
+    "writeSharedRWMemory",
+    "setInputSignal",
+    "getWitnessSize",
+    "getWitness",
+];
+
+/// Checks that `prime`, given as little endian [`u32`] limbs, is the modulus of the field `F`.
+///
+/// # Errors
+///
+/// Returns a [`NonMatchingPrime`] error if `prime` and the modulus of `F` differ.
+pub fn check_prime<F: PrimeField>(prime: &[u32]) -> Result<()> {
+    let prime = trim_limbs(prime);
+    let modulus = modulus_as_limbs::<F>();
+    if modulus.as_deref() != Some(prime) {
+        return Err(NonMatchingPrime {
+            expected: modulus.map_or_else(|| F::MODULUS.to_string(), |m| limbs_as_hex(&m)),
+            value: limbs_as_hex(prime),
+        });
+    }
+
+    Ok(())
 }
 
-impl Circom for Wasm {
-    fn init(&self, store: &mut impl AsStoreMut, sanity_check: bool) -> Result<()> {
-        let func = self.func("init");
-        func.call(store, &[Value::I32(i32::from(sanity_check))])?;
+/// Base trait for interacting with Circom WASM instances.
+///
+/// The trait is independent of the engine running the instance: engines provide the hooks to
+/// compile, instantiate and reset instances, along with [`Circom::has_func`] and [`Circom::call`],
+/// on top of which the functions exported by circom are called. A
+/// [`WitnessCalculator`](super::WitnessCalculator) runs on any engine implementing it.
+pub trait Circom: fmt::Debug + Sized {
+    /// The store holding the state of the instance.
+    type Store: fmt::Debug;
+
+    /// A compiled WebAssembly module, from which instances are created.
+    type Module: Clone + fmt::Debug;
+
+    /// Compiles the WebAssembly module in the file `path`.
+    fn compile(path: &Path) -> Result<Self::Module>;
+
+    /// Instantiates `module` in a new store.
+    fn instantiate(module: &Self::Module) -> Result<(Self, Self::Store)>;
+
+    /// Returns whether the instance exports a function called `name`.
+    fn has_func(&self, name: &str) -> bool;
+
+    /// Calls the exported function `name` with `i32` arguments, returning its result if it has one.
+    ///
+    /// Errors reported by the circuit itself, such as circom 2 exceptions, are returned as their
+    /// [`WitnessCalculatorError`](super::WitnessCalculatorError).
+    fn call(&self, store: &mut Self::Store, name: &str, args: &[u32]) -> Result<Option<u32>>;
+
+    /// Returns the messages printed by the circuit, which the runtime hooks of the engine keep.
+    fn messages<'a>(&self, store: &'a mut Self::Store) -> &'a mut Messages;
+
+    /// Prepares the instance to calculate the witnesses of a circuit compiled with circom `version`,
+    /// whose field elements are made of `n32` 32-bit limbs, and returns the little endian limbs of
+    /// the prime the circuit was compiled for.
+    ///
+    /// By default, only circom 2 circuits are supported, whose prime is read through the shared
+    /// read-write memory.
+    fn prepare(&mut self, store: &mut Self::Store, version: u32, n32: u32) -> Result<Vec<u32>> {
+        match version {
+            2 => {
+                self.get_raw_prime(store)?;
+                self.read_shared_rw_memory_limbs(store, n32)
+            }
+            version => Err(VersionNotSupported(version)),
+        }
+    }
+
+    /// Brings the instance back to a state in which it calculates witnesses as if it was just
+    /// prepared, after a calculation that may have left it in an inconsistent state.
+    fn reset(&mut self, store: &mut Self::Store) -> Result<()>;
+
+    /// Calculates the witness of a circom 1 circuit, whose inputs and witness go through the memory
+    /// of the instance.
+    ///
+    /// By default, circom 1 circuits are not supported.
+    fn calculate_witness_circom1<F: PrimeFieldBits>(
+        &mut self,
+        _store: &mut Self::Store,
+        _inputs: Vec<CircomInput<F>>,
+    ) -> Result<Vec<F>> {
+        Err(VersionNotSupported(1))
+    }
+
+    /// Reads the `n32` limbs held in the shared read-write memory of a circom 2 circuit, limb by
+    /// limb through `readSharedRWMemory`.
+    fn read_shared_rw_memory_limbs(&self, store: &mut Self::Store, n32: u32) -> Result<Vec<u32>> {
+        (0..n32)
+            .map(|j| self.read_shared_rw_memory(store, j))
+            .collect()
+    }
+
+    /// Writes limbs to the shared read-write memory of a circom 2 circuit, limb by limb through
+    /// `writeSharedRWMemory`.
+    fn write_shared_rw_memory_limbs(&self, store: &mut Self::Store, limbs: &[u32]) -> Result<()> {
+        limbs
+            .iter()
+            .enumerate()
+            .try_for_each(|(j, &limb)| self.write_shared_rw_memory(store, j as u32, limb))
+    }
+
+    /// Calls the exported function `name`, which returns a single `i32`.
+    fn call_u32(&self, store: &mut Self::Store, name: &str, args: &[u32]) -> Result<u32> {
+        self.call(store, name, args)?
+            .ok_or_else(|| UnexpectedReturn(name.to_string()))
+    }
+
+    /// Returns the functions the witness generator of the given circom version must export that the
+    /// instance does not.
+    fn missing_exports(&self, version: u32) -> Vec<String> {
+        let required = match version {
+            1 => CIRCOM1_EXPORTS,
+            _ => CIRCOM2_EXPORTS,
+        };
+        required
+            .iter()
+            .filter(|name| !self.has_func(name))
+            .map(|name| name.to_string())
+            .collect()
+    }
+
+    fn init(&self, store: &mut Self::Store, sanity_check: bool) -> Result<()> {
+        self.call(store, "init", &[u32::from(sanity_check)])?;
         Ok(())
     }
 
-    fn get_ptr_witness_buffer(&self, store: &mut impl AsStoreMut) -> Result<u32> {
+    fn get_ptr_witness_buffer(&self, store: &mut Self::Store) -> Result<u32> {
         self.get_u32(store, "getWitnessBuffer")
     }
 
-    fn get_ptr_witness(&self, store: &mut impl AsStoreMut, w: u32) -> Result<u32> {
-        let func = self.func("getPWitness");
-        let res = func.call(store, &[w.into()])?;
-
-        Ok(res[0].unwrap_i32() as u32)
+    fn get_ptr_witness(&self, store: &mut Self::Store, w: u32) -> Result<u32> {
+        self.call_u32(store, "getPWitness", &[w])
     }
 
-    fn get_n_vars(&self, store: &mut impl AsStoreMut) -> Result<u32> {
+    fn get_n_vars(&self, store: &mut Self::Store) -> Result<u32> {
         self.get_u32(store, "getNVars")
     }
 
     fn get_signal_offset32(
         &self,
-        store: &mut impl AsStoreMut,
+        store: &mut Self::Store,
         p_sig_offset: u32,
         component: u32,
         hash_msb: u32,
         hash_lsb: u32,
     ) -> Result<()> {
-        let func = self.func("getSignalOffset32");
-        func.call(
+        self.call(
             store,
-            &[
-                p_sig_offset.into(),
-                component.into(),
-                hash_msb.into(),
-                hash_lsb.into(),
-            ],
+            "getSignalOffset32",
+            &[p_sig_offset, component, hash_msb, hash_lsb],
         )?;
 
         Ok(())
@@ -103,96 +204,139 @@ impl Circom for Wasm {
 
     fn set_signal(
         &self,
-        store: &mut impl AsStoreMut,
+        store: &mut Self::Store,
         c_idx: u32,
         component: u32,
         signal: u32,
         p_val: u32,
     ) -> Result<()> {
-        let func = self.func("setSignal");
-        func.call(
-            store,
-            &[c_idx.into(), component.into(), signal.into(), p_val.into()],
-        )?;
+        self.call(store, "setSignal", &[c_idx, component, signal, p_val])?;
 
         Ok(())
     }
 
-    // Default to version 1 if it isn't explicitly defined
-    fn get_version(&self, store: &mut impl AsStoreMut) -> Result<u32> {
-        match self.0.exports.get_function("getVersion") {
-            Ok(func) => Ok(func.call(store, &[])?[0].unwrap_i32() as u32),
-            Err(_) => Ok(1),
-        }
+    fn get_u32(&self, store: &mut Self::Store, name: &str) -> Result<u32> {
+        self.call_u32(store, name, &[])
     }
 
-    fn get_u32(&self, store: &mut impl AsStoreMut, name: &str) -> Result<u32> {
-        let func = self.func(name);
-        let result = func.call(store, &[])?;
-        Ok(result[0].unwrap_i32() as u32)
+    // Default to version 1 if it isn't explicitly defined
+    fn get_version(&self, store: &mut Self::Store) -> Result<u32> {
+        if self.has_func("getVersion") {
+            self.get_u32(store, "getVersion")
+        } else {
+            Ok(1)
+        }
     }
 
-    fn func(&self, name: &str) -> &Function {
-        self.0
-            .exports
-            .get_function(name)
-            .unwrap_or_else(|_| panic!("function {} not found", name))
-    }
-    fn get_fr_len(&self, store: &mut impl AsStoreMut) -> Result<u32> {
+    fn get_fr_len(&self, store: &mut Self::Store) -> Result<u32> {
         self.get_u32(store, "getFrLen")
     }
 
-    fn get_ptr_raw_prime(&self, store: &mut impl AsStoreMut) -> Result<u32> {
+    fn get_ptr_raw_prime(&self, store: &mut Self::Store) -> Result<u32> {
         self.get_u32(store, "getPRawPrime")
     }
 
-    fn get_field_num_len32(&self, store: &mut impl AsStoreMut) -> Result<u32> {
+    fn get_field_num_len32(&self, store: &mut Self::Store) -> Result<u32> {
         self.get_u32(store, "getFieldNumLen32")
     }
 
-    fn get_raw_prime(&self, store: &mut impl AsStoreMut) -> Result<()> {
-        let func = self.func("getRawPrime");
-        func.call(store, &[])?;
+    fn get_shared_rw_memory_start(&self, store: &mut Self::Store) -> Result<u32> {
+        self.get_u32(store, "getSharedRWMemoryStart")
+    }
+
+    fn get_raw_prime(&self, store: &mut Self::Store) -> Result<()> {
+        self.call(store, "getRawPrime", &[])?;
         Ok(())
     }
 
-    fn read_shared_rw_memory(&self, store: &mut impl AsStoreMut, i: u32) -> Result<u32> {
-        let func = self.func("readSharedRWMemory");
-        let result = func.call(store, &[i.into()])?;
-        Ok(result[0].unwrap_i32() as u32)
+    fn read_shared_rw_memory(&self, store: &mut Self::Store, i: u32) -> Result<u32> {
+        self.call_u32(store, "readSharedRWMemory", &[i])
     }
 
-    fn write_shared_rw_memory(&self, store: &mut impl AsStoreMut, i: u32, v: u32) -> Result<()> {
-        let func = self.func("writeSharedRWMemory");
-        func.call(store, &[i.into(), v.into()])?;
+    fn write_shared_rw_memory(&self, store: &mut Self::Store, i: u32, v: u32) -> Result<()> {
+        self.call(store, "writeSharedRWMemory", &[i, v])?;
         Ok(())
     }
 
     fn set_input_signal(
         &self,
-        store: &mut impl AsStoreMut,
+        store: &mut Self::Store,
         hmsb: u32,
         hlsb: u32,
         pos: u32,
     ) -> Result<()> {
-        let func = self.func("setInputSignal");
-        func.call(store, &[hmsb.into(), hlsb.into(), pos.into()])?;
+        self.call(store, "setInputSignal", &[hmsb, hlsb, pos])?;
         Ok(())
     }
 
-    fn get_witness(&self, store: &mut impl AsStoreMut, i: u32) -> Result<()> {
-        let func = self.func("getWitness");
-        func.call(store, &[i.into()])?;
+    fn get_input_signal_size(&self, store: &mut Self::Store, hmsb: u32, hlsb: u32) -> Result<u32> {
+        self.call_u32(store, "getInputSignalSize", &[hmsb, hlsb])
+    }
+
+    fn get_input_size(&self, store: &mut Self::Store) -> Result<u32> {
+        self.get_u32(store, "getInputSize")
+    }
+
+    fn get_witness(&self, store: &mut Self::Store, i: u32) -> Result<()> {
+        self.call(store, "getWitness", &[i])?;
         Ok(())
     }
 
-    fn get_witness_size(&self, store: &mut impl AsStoreMut) -> Result<u32> {
+    fn get_witness_size(&self, store: &mut Self::Store) -> Result<u32> {
         self.get_u32(store, "getWitnessSize")
     }
-}
 
-impl Wasm {
-    pub fn new(instance: Instance) -> Self {
-        Self(instance)
+    /// Checks the inputs against the input signals of a circom 2 circuit, see
+    /// [`super::WitnessCalculator::check_inputs`].
+    ///
+    /// The missing inputs are looked up in `symbols`, if given.
+    fn check_inputs<F: PrimeField>(
+        &self,
+        store: &mut Self::Store,
+        inputs: &[CircomInput<F>],
+        symbols: Option<&SymbolTable>,
+    ) -> Result<()> {
+        if !self.has_func("getInputSignalSize") || !self.has_func("getInputSize") {
+            return Ok(());
+        }
+
+        for input in inputs {
+            let (msb, lsb) = fnv(&input.name);
+            let expected = self.get_input_signal_size(store, msb, lsb)?;
+            if expected == 0 {
+                return Err(UnknownInput {
+                    name: input.name.clone(),
+                });
+            }
+            if expected as usize != input.value.len() {
+                return Err(InputLengthMismatch {
+                    name: input.name.clone(),
+                    expected,
+                    actual: input.value.len(),
+                });
+            }
+        }
+
+        let expected = self.get_input_size(store)?;
+        let actual = inputs.iter().map(|input| input.value.len()).sum::<usize>();
+        if actual < expected as usize {
+            let mut missing = vec![];
+            for name in symbols.iter().flat_map(|symbols| symbols.main_signals()) {
+                if inputs.iter().any(|input| input.name == name) {
+                    continue;
+                }
+                let (msb, lsb) = fnv(name);
+                if self.get_input_signal_size(store, msb, lsb)? != 0 {
+                    missing.push(name.to_string());
+                }
+            }
+            return Err(MissingInputs {
+                expected,
+                actual,
+                missing,
+            });
+        }
+
+        Ok(())
     }
 }
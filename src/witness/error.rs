@@ -1,5 +1,196 @@
 use thiserror::Error;
 
+/// Result of the operations of the witness calculator.
+pub(crate) type Result<T> = std::result::Result<T, WitnessCalculatorError>;
+
 /// Enum related to witness generatiuon problems.
 #[derive(Error, Debug)]
-pub enum WitnessCalculatorError {}
+pub enum WitnessCalculatorError {
+    /// Error thrown if the WebAssembly module cannot be read or compiled.
+    #[cfg(feature = "wasmer")]
+    #[error("Failed to load the WebAssembly module: {0}")]
+    ModuleLoad(#[from] wasmer::IoCompileError),
+    /// Error thrown if the WebAssembly module cannot be instantiated.
+    #[cfg(feature = "wasmer")]
+    #[error("Failed to instantiate the WebAssembly module: {0}")]
+    Instantiation(#[from] Box<wasmer::InstantiationError>),
+    /// Error thrown if the memory provided to the WebAssembly module cannot be created.
+    #[cfg(feature = "wasmer")]
+    #[error("Failed to create the WebAssembly memory: {0}")]
+    Memory(#[from] wasmer::MemoryError),
+    /// Error thrown if the WebAssembly module does not export functions a witness generator should.
+    #[error("The WebAssembly module does not export: {}", .0.join(", "))]
+    MissingExports(Vec<String>),
+    /// Error thrown if a function exported by the WebAssembly module does not return a single `i32`.
+    #[error("The exported function {0} does not return a single i32.")]
+    UnexpectedReturn(String),
+    /// Error thrown if the witness generator was compiled with a version of circom we do not support.
+    #[error("Witness generator version not supported. Versions supported are 1 and 2, found {0}")]
+    VersionNotSupported(u32),
+    /// Error thrown if the prime the circuit was compiled for does not match the modulus of the field
+    /// used to calculate the witness.
+    #[error("Mismatched prime field. Expected {expected}, the circuit was compiled for {value} instead.")]
+    NonMatchingPrime { expected: String, value: String },
+    /// Error thrown if the circuit reports a field element size we cannot work with.
+    #[error("Unsupported field element size of {0} 32-bit limbs.")]
+    UnsupportedFieldSize(u32),
+    /// Error thrown if an input does not name an input signal of the circuit.
+    #[error("Input signal \"{name}\" not found in the circuit.")]
+    UnknownInput { name: String },
+    /// Error thrown if an input does not hold as many values as its signal.
+    #[error("Input signal \"{name}\" expects {expected} values, got {actual}.")]
+    InputLengthMismatch {
+        name: String,
+        expected: u32,
+        actual: usize,
+    },
+    /// Error thrown if fewer input values are given than the circuit expects. The names of the
+    /// missing inputs are only known if the circuit symbols were provided.
+    #[error("Missing inputs. Expected {expected} input values, got {actual}.{}", if missing.is_empty() { String::new() } else { format!(" Missing: {}", missing.join(", ")) })]
+    MissingInputs {
+        expected: u32,
+        actual: usize,
+        missing: Vec<String>,
+    },
+    /// Error thrown by the circuit if an input signal does not exist.
+    #[error("Signal not found.")]
+    SignalNotFound,
+    /// Error thrown by the circuit if more values are set for a signal than its size.
+    #[error("Too many values set for a signal.")]
+    TooManySignalValues,
+    /// Error thrown by the circuit if a signal is set twice.
+    #[error("Signal already set.")]
+    SignalAlreadySet,
+    /// Error thrown by the circuit if one of its assertions does not hold.
+    #[error("Assert failed.{}", error_buffer.iter().map(|message| format!("\n{message}")).collect::<String>())]
+    AssertFailed {
+        /// Name of the template whose assertion failed, as reported by the circuit.
+        template: Option<String>,
+        /// Line of the failed assertion in the template source, as reported by the circuit.
+        line: Option<u32>,
+        /// Message printed by the failing template.
+        message: Option<String>,
+        /// All error messages printed by the circuit before failing, innermost template first.
+        error_buffer: Vec<String>,
+    },
+    /// Error thrown by the circuit if it runs out of memory.
+    #[error("Not enough memory.")]
+    NotEnoughMemory,
+    /// Error thrown by the circuit if an input signal array is accessed past its size.
+    #[error("Input signal array access exceeds the size.")]
+    InputArrayIndexOutOfRange,
+    /// Error thrown by the circuit with an exception code we do not know of.
+    #[error("Unknown exception code {0} reported by the circuit.")]
+    UnknownException(i32),
+    /// Error reported by a circom 1 circuit through its `error` hook.
+    #[error("Circuit error {code}: {message}")]
+    CircuitError { code: i32, message: String },
+    /// Error thrown if the WebAssembly memory is accessed out of its bounds.
+    #[cfg(feature = "wasmer")]
+    #[error("Invalid access to the WebAssembly memory: {0}")]
+    MemoryAccess(#[from] wasmer::MemoryAccessError),
+    /// Error thrown if the WebAssembly instance traps for a reason other than an error reported by the
+    /// circuit.
+    #[cfg(feature = "wasmer")]
+    #[error("WebAssembly runtime error: {0}")]
+    Runtime(#[from] wasmer::RuntimeError),
+    /// Error thrown if a file cannot be read or written, such as the WebAssembly module of a circuit
+    /// run by wasmi, or the files of the native witness generator.
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    /// Error thrown if the native witness generator does not exit successfully.
+    #[error("The native witness generator failed with {status}: {output}")]
+    GeneratorFailed {
+        status: std::process::ExitStatus,
+        output: String,
+    },
+    /// Error thrown if the witness written by the native witness generator cannot be read.
+    #[error("Failed to read the witness of the native witness generator: {0}")]
+    ReadWitness(#[from] crate::error::ReaderError),
+    /// Error thrown by the wasmi interpreter, when loading, instantiating or running the WebAssembly
+    /// module, for a reason other than an error reported by the circuit.
+    #[cfg(feature = "wasmi")]
+    #[error("wasmi error: {0}")]
+    Wasmi(wasmi::Error),
+    /// Error thrown if a witness cache is used with a calculator that does not run the module of the
+    /// cache.
+    #[cfg(feature = "cache")]
+    #[error("The witness calculator does not run the WebAssembly module of the witness cache.")]
+    NonMatchingModule,
+    /// Error thrown if a WebAssembly module run by wasmi is neither in the binary nor in the text
+    /// format.
+    #[cfg(feature = "wasmi")]
+    #[error("Failed to parse the WebAssembly module: {0}")]
+    Wat(#[from] wat::Error),
+}
+
+#[cfg(feature = "wasmer")]
+impl From<wasmer::InstantiationError> for WitnessCalculatorError {
+    fn from(err: wasmer::InstantiationError) -> Self {
+        Box::new(err).into()
+    }
+}
+
+#[cfg(feature = "wasmi")]
+impl wasmi::core::HostError for WitnessCalculatorError {}
+
+#[cfg(feature = "wasmi")]
+impl From<wasmi::Error> for WitnessCalculatorError {
+    fn from(err: wasmi::Error) -> Self {
+        // Errors reported by the circuit are raised by the runtime hooks as host errors.
+        if err.downcast_ref::<Self>().is_none() {
+            return Self::Wasmi(err);
+        }
+        err.downcast::<Self>()
+            .expect("the host error is a WitnessCalculatorError")
+    }
+}
+
+impl WitnessCalculatorError {
+    /// Maps an exception code reported through circom 2's `exceptionHandler` to its error.
+    ///
+    /// `error_buffer` holds the messages the circuit printed through `printErrorMessage` beforehand.
+    pub(crate) fn from_exception_code(code: i32, error_buffer: Vec<String>) -> Self {
+        match code {
+            1 => Self::SignalNotFound,
+            2 => Self::TooManySignalValues,
+            3 => Self::SignalAlreadySet,
+            4 => Self::assert_failed(error_buffer),
+            5 => Self::NotEnoughMemory,
+            6 => Self::InputArrayIndexOutOfRange,
+            code => Self::UnknownException(code),
+        }
+    }
+
+    /// Maps an error reported through circom 1's `error` hook to its error. Circom 1 reports
+    /// unsatisfied constraints and assertions with code 7, other codes are kept as a
+    /// [`WitnessCalculatorError::CircuitError`].
+    #[cfg(feature = "wasmer")]
+    pub(crate) fn from_circom1_error(code: i32, message: String) -> Self {
+        match code {
+            7 => Self::assert_failed(vec![message]),
+            code => Self::CircuitError { code, message },
+        }
+    }
+
+    /// Builds an [`WitnessCalculatorError::AssertFailed`] from the error messages of the circuit.
+    /// The first one comes from the failing template and reads `Error in template <name> line: <line>`.
+    fn assert_failed(error_buffer: Vec<String>) -> Self {
+        let message = error_buffer.first().cloned();
+        let location = message.as_deref().and_then(|message| {
+            let (template, line) = message
+                .strip_prefix("Error in template ")?
+                .split_once(" line: ")?;
+            Some((template.to_string(), line.trim().parse().ok()))
+        });
+        let (template, line) =
+            location.map_or((None, None), |(template, line)| (Some(template), line));
+
+        Self::AssertFailed {
+            template,
+            line,
+            message,
+            error_buffer,
+        }
+    }
+}
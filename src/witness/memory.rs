@@ -6,14 +6,13 @@
 //! This module provides the `SafeMemory` struct and associated methods for efficient and secure memory management in
 //! WASM (WebAssembly) computations, particularly focused on Circom circuit calculations.
 
-use ff::PrimeField;
-use ruint::aliases::U256;
-use wasmer::{AsStoreRef, Memory, MemoryView};
+use ff::{PrimeField, PrimeFieldBits};
+use wasmer::{AsStoreRef, Memory, MemoryAccessError, MemoryView};
 
-use anyhow::Result;
+use super::Result;
 use std::ops::Deref;
 
-use crate::util::u256_as_ff;
+use crate::util::{ff_as_limbs, limbs_as_ff, trim_limbs};
 
 /// A wrapper around the [`wasmer::Memory`] object, providing additional functionality
 /// and safety checks specific to Circom computations.
@@ -21,12 +20,8 @@ use crate::util::u256_as_ff;
 pub struct SafeMemory {
     /// The underlying WebAssembly memory instance.
     pub memory: Memory,
-    /// A [`U256` ]representing the prime field used in computations.
-    pub prime: U256,
-    /// The maximum value for a short field element.
-    short_max: U256,
-    /// The minimum value for a short field element.
-    short_min: U256,
+    /// The little endian [`u32`] limbs of the prime field used in computations.
+    pub prime: Vec<u32>,
     /// The size of the memory chunks, in 32-bit units.
     n32: usize,
 }
@@ -47,19 +42,14 @@ impl SafeMemory {
     ///
     /// * `memory` - A [`wasmer::Memory`] instance representing the WebAssembly memory.
     /// * `n32` - The size of memory chunks, expressed in 32-bit units.
-    /// * `prime` - A [`U256`] prime field used in cryptographic computations.
-    pub fn new(memory: Memory, n32: usize, prime: U256) -> Self {
-        // TODO: Figure out a better way to calculate these
-        let short_max = U256::from(0x8000_0000u64);
-        let short_min = short_max.wrapping_neg().reduce_mod(prime);
-
-        Self {
-            memory,
-            prime,
-            short_max,
-            short_min,
-            n32,
-        }
+    /// * `prime` - The little endian [`u32`] limbs of the prime field used in cryptographic computations.
+    pub fn new(memory: Memory, n32: usize, prime: Vec<u32>) -> Self {
+        Self { memory, prime, n32 }
+    }
+
+    /// Returns the size of the memory chunks, in 32-bit units.
+    pub fn n32(&self) -> usize {
+        self.n32
     }
 
     /// Gets an immutable view of the memory in 32-byte chunks.
@@ -76,7 +66,11 @@ impl SafeMemory {
     /// # Arguments
     ///
     /// * `store` - A reference to the store that holds the WebAssembly memory.
-    pub fn free_pos(&self, store: &impl AsStoreRef) -> u32 {
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`MemoryAccess`](super::WitnessCalculatorError::MemoryAccess) error if the memory is empty.
+    pub fn free_pos(&self, store: &impl AsStoreRef) -> Result<u32> {
         self.read_u32(store, 0)
     }
 
@@ -86,8 +80,12 @@ impl SafeMemory {
     ///
     /// * `store` - A reference to the store that holds the WebAssembly memory.
     /// * `ptr` - The memory address to set as the next free position.
-    pub fn set_free_pos(&mut self, store: &impl AsStoreRef, ptr: u32) {
-        self.write_u32(store, 0, ptr);
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`MemoryAccess`](super::WitnessCalculatorError::MemoryAccess) error if the memory is empty.
+    pub fn set_free_pos(&mut self, store: &impl AsStoreRef, ptr: u32) -> Result<()> {
+        self.write_u32(store, 0, ptr)
     }
 
     /// Allocates space for a [`u32`] value in memory and returns its pointer.
@@ -95,10 +93,12 @@ impl SafeMemory {
     /// # Arguments
     ///
     /// * `store` - A reference to the store that holds the WebAssembly memory.
-    pub fn alloc_u32(&mut self, store: &impl AsStoreRef) -> u32 {
-        let p = self.free_pos(store);
-        self.set_free_pos(store, p + 8);
-        p
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`MemoryAccess`](super::WitnessCalculatorError::MemoryAccess) error if the memory is empty, or if the free position would overflow.
+    pub fn alloc_u32(&mut self, store: &impl AsStoreRef) -> Result<u32> {
+        self.alloc(store, 8)
     }
 
     /// Writes a [`u32`] value to a specified memory offset.
@@ -108,10 +108,13 @@ impl SafeMemory {
     /// * `store` - A reference to the store that holds the WebAssembly memory.
     /// * `ptr` - The memory address where the [`u32`] value will be written.
     /// * `num` - The [`u32`] value to write.
-    pub fn write_u32(&mut self, store: &impl AsStoreRef, ptr: usize, num: u32) {
-        let view = self.view(store);
-        let buf = unsafe { view.data_unchecked_mut() };
-        buf[ptr..ptr + std::mem::size_of::<u32>()].copy_from_slice(&num.to_le_bytes());
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`MemoryAccess`](super::WitnessCalculatorError::MemoryAccess) error if the value does not fit within the memory.
+    pub fn write_u32(&mut self, store: &impl AsStoreRef, ptr: usize, num: u32) -> Result<()> {
+        self.view(store).write(ptr as u64, &num.to_le_bytes())?;
+        Ok(())
     }
 
     /// Reads a [`u32`] value from a specified memory offset.
@@ -120,14 +123,33 @@ impl SafeMemory {
     ///
     /// * `store` - A reference to the store that holds the WebAssembly memory.
     /// * `ptr` - The memory address from where the [`u32`] value will be read.
-    pub fn read_u32(&self, store: &impl AsStoreRef, ptr: usize) -> u32 {
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`MemoryAccess`](super::WitnessCalculatorError::MemoryAccess) error if the value is not within the memory.
+    pub fn read_u32(&self, store: &impl AsStoreRef, ptr: usize) -> Result<u32> {
+        let mut bytes = [0; 4];
+        self.view(store).read(ptr as u64, &mut bytes)?;
+
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    /// Reads a null terminated string from a specified memory offset.
+    ///
+    /// # Arguments
+    ///
+    /// * `store` - A reference to the store that holds the WebAssembly memory.
+    /// * `ptr` - The memory address from where the string will be read.
+    pub fn read_string(&self, store: &impl AsStoreRef, ptr: usize) -> String {
         let view = self.view(store);
         let buf = unsafe { view.data_unchecked() };
 
-        let mut bytes = [0; 4];
-        bytes.copy_from_slice(&buf[ptr..ptr + std::mem::size_of::<u32>()]);
-
-        u32::from_le_bytes(bytes)
+        buf.get(ptr..)
+            .unwrap_or_default()
+            .iter()
+            .take_while(|&&c| c != 0)
+            .map(|&c| char::from(c))
+            .collect()
     }
 
     /// Allocates `self.n32 * 4 + 8` space for a field element in the memory and returns its pointer.
@@ -135,23 +157,41 @@ impl SafeMemory {
     /// # Arguments
     ///
     /// * `store` - A reference to the store that holds the WebAssembly memory.
-    pub fn alloc_fr(&mut self, store: &impl AsStoreRef) -> u32 {
-        let p = self.free_pos(store);
-        self.set_free_pos(store, p + self.n32 as u32 * 4 + 8);
-        p
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`MemoryAccess`](super::WitnessCalculatorError::MemoryAccess) error if the memory is empty, or if the free position would overflow.
+    pub fn alloc_fr(&mut self, store: &impl AsStoreRef) -> Result<u32> {
+        let size = u32::try_from(self.n32 * 4 + 8).map_err(|_| MemoryAccessError::Overflow)?;
+        self.alloc(store, size)
+    }
+
+    /// Bumps the free position by `size` bytes and returns its previous value.
+    fn alloc(&mut self, store: &impl AsStoreRef, size: u32) -> Result<u32> {
+        let p = self.free_pos(store)?;
+        let next = p.checked_add(size).ok_or(MemoryAccessError::Overflow)?;
+        self.set_free_pos(store, next)?;
+        Ok(p)
     }
 
-    /// Writes a field element ([`U256`]) to memory at the specified offset, truncating
-    /// to smaller [`u32`] types if needed and adjusting the sign via 2s complement
+    /// Writes a field element to memory at the specified offset. Field elements that fit in an [`i32`],
+    /// once their sign is adjusted via 2s complement, are written in their short form.
     ///
     /// # Arguments
     ///
     /// * `store` - A reference to the store that holds the WebAssembly memory.
     /// * `ptr` - The memory address where the field element will be written.
-    /// * `fr` - The [`U256`] field element to write.
-    pub fn write_fr(&mut self, store: &impl AsStoreRef, ptr: usize, fr: U256) -> Result<()> {
-        if fr < self.short_max && fr > self.short_min {
-            self.write_short(store, ptr, fr)?;
+    /// * `fr` - The field element to write.
+    pub fn write_fr<F: PrimeFieldBits>(
+        &mut self,
+        store: &impl AsStoreRef,
+        ptr: usize,
+        fr: F,
+    ) -> Result<()> {
+        if let Some(num) = as_short(fr) {
+            self.write_short(store, ptr, num)?;
+        } else if let Some(num) = as_short(-fr) {
+            self.write_short(store, ptr, num.wrapping_neg())?;
         } else {
             self.write_long_normal(store, ptr, fr)?;
         }
@@ -159,75 +199,216 @@ impl SafeMemory {
         Ok(())
     }
 
-    /// Reads a field element ([`PrimeField`]) from the memory at the specified offset.
+    /// Reads a field element ([`PrimeField`]) from the memory at the specified offset, handling its
+    /// short, long and Montgomery forms.
     ///
     /// # Arguments
     ///
     /// * `store` - A reference to the store that holds the WebAssembly memory.
     /// * `ptr` - The memory address from where the field element will be read.
-    pub fn read_fr<F: PrimeField>(&self, store: &impl AsStoreRef, ptr: usize) -> F {
-        let view = self.view(store);
-        let view = unsafe { view.data_unchecked_mut() };
-
-        if view[ptr + 7] & 0x80 != 0 {
-            let num = self.read_big(store, ptr + 8);
-            u256_as_ff(num)
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`MemoryAccess`](super::WitnessCalculatorError::MemoryAccess) error if the field
+    /// element is not within the memory.
+    pub fn read_fr<F: PrimeField>(&self, store: &impl AsStoreRef, ptr: usize) -> Result<F> {
+        let mut header = [0; 8];
+        self.view(store).read(ptr as u64, &mut header)?;
+        let [n0, n1, n2, n3, f0, f1, f2, f3] = header;
+        let flags = u32::from_le_bytes([f0, f1, f2, f3]);
+        if flags & 0x8000_0000 != 0 {
+            let res: F = limbs_as_ff(&self.read_big(store, ptr + 8)?);
+            if flags & 0x4000_0000 != 0 {
+                // Long elements may be stored in Montgomery form, with R = 2^(32 * n32).
+                Ok(res * F::TWO_INV.pow_vartime([(self.n32 * 32) as u64]))
+            } else {
+                Ok(res)
+            }
         } else {
-            F::from(u64::from(self.read_u32(store, ptr)))
+            let num = i32::from_le_bytes([n0, n1, n2, n3]);
+            let res = F::from(u64::from(num.unsigned_abs()));
+            if num < 0 {
+                Ok(-res)
+            } else {
+                Ok(res)
+            }
         }
     }
 
-    /// Writes a short field element to memory. Short elements are smaller than the upper limit of the prime field,
-    /// and thus can be stored more efficiently.
+    /// Writes a short field element to memory. Short elements fit in an [`i32`], and thus can be
+    /// stored more efficiently.
     ///
     /// # Arguments
     ///
     /// * `store` - A reference to the store that holds the WebAssembly memory.
     /// * `ptr` - The memory address where the field element will be written.
-    /// * `fr` - The [`U256`] field element to write.
-    fn write_short(&mut self, store: &impl AsStoreRef, ptr: usize, fr: U256) -> Result<()> {
-        let num = fr.as_limbs()[0] as u32;
-        self.write_u32(store, ptr, num);
-        self.write_u32(store, ptr + 4, 0);
-        Ok(())
+    /// * `num` - The short field element to write, in 2s complement.
+    fn write_short(&mut self, store: &impl AsStoreRef, ptr: usize, num: u32) -> Result<()> {
+        self.write_u32(store, ptr, num)?;
+        self.write_u32(store, ptr + 4, 0)
     }
 
-    fn write_long_normal(&mut self, store: &impl AsStoreRef, ptr: usize, fr: U256) -> Result<()> {
-        self.write_u32(store, ptr, 0);
-        self.write_u32(store, ptr + 4, i32::MIN as u32); // 0x80000000
-        self.write_big(store, ptr + 8, fr)?;
-        Ok(())
+    fn write_long_normal<F: PrimeFieldBits>(
+        &mut self,
+        store: &impl AsStoreRef,
+        ptr: usize,
+        fr: F,
+    ) -> Result<()> {
+        self.write_u32(store, ptr, 0)?;
+        self.write_u32(store, ptr + 4, i32::MIN as u32)?; // 0x80000000
+        self.write_big(store, ptr + 8, &ff_as_limbs(fr, self.n32))
     }
 
-    // Writes a long field element in its normal form to memory. This method is used for elements that do not fit
-    /// into the short form.
+    /// Writes the little endian limbs of a big integer to memory, in a single write.
     ///
     /// # Arguments
     ///
     /// * `store` - A reference to the store that holds the WebAssembly memory.
-    /// * `ptr` - The memory address where the field element will be written.
-    /// * `fr` - The [`U256`] field element to write.
-    fn write_big(&self, store: &impl AsStoreRef, ptr: usize, num: U256) -> Result<()> {
-        let view = self.view(store);
-        let buf = unsafe { view.data_unchecked_mut() };
-
-        let bytes: [u8; 32] = num.to_le_bytes();
-        buf[ptr..ptr + 32].copy_from_slice(&bytes);
+    /// * `ptr` - The memory address where the big integer will be written.
+    /// * `limbs` - The little endian [`u32`] limbs of the big integer to write.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`MemoryAccess`](super::WitnessCalculatorError::MemoryAccess) error if the big
+    /// integer does not fit within the memory.
+    pub fn write_big(&self, store: &impl AsStoreRef, ptr: usize, limbs: &[u32]) -> Result<()> {
+        let bytes = limbs
+            .iter()
+            .flat_map(|limb| limb.to_le_bytes())
+            .collect::<Vec<_>>();
+        self.view(store).write(ptr as u64, &bytes)?;
 
         Ok(())
     }
 
-    /// Reads a big integer ([`U256`]) from the specified memory offset.
-    /// This method reads `num_bytes * 32` from memory and returns it as a [`U256`] big integer.
+    /// Reads a big integer from the specified memory offset.
+    /// This method reads `n32` little endian [`u32`] limbs from memory, in a single read.
     ///
     /// # Arguments
     ///
     /// * `store` - A reference to the store that holds the WebAssembly memory.
     /// * `ptr` - The memory address from where the big integer will be read.
-    pub fn read_big(&self, store: &impl AsStoreRef, ptr: usize) -> U256 {
-        let view = self.view(store);
-        let buf = unsafe { view.data_unchecked() };
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`MemoryAccess`](super::WitnessCalculatorError::MemoryAccess) error if the big
+    /// integer is not within the memory.
+    pub fn read_big(&self, store: &impl AsStoreRef, ptr: usize) -> Result<Vec<u32>> {
+        let mut bytes = vec![0; self.n32 * 4];
+        self.view(store).read(ptr as u64, &mut bytes)?;
+
+        Ok(bytes
+            .chunks_exact(4)
+            .map(|chunk| u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+            .collect())
+    }
+}
+
+/// Returns the value of a field element if it is positive and fits in an [`i32`].
+fn as_short<F: PrimeFieldBits>(fr: F) -> Option<u32> {
+    let limbs = ff_as_limbs(fr, (F::NUM_BITS as usize + 31) / 32);
+    match trim_limbs(&limbs) {
+        [] => Some(0),
+        [num] if *num < 0x8000_0000 => Some(*num),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ff::Field;
+    use pasta_curves::vesta;
+    use wasmer::{MemoryType, Store};
+
+    use super::*;
+    use crate::util::modulus_as_limbs;
+    use crate::witness::WitnessCalculatorError;
+
+    #[test]
+    fn test_fr_roundtrip() {
+        let mut store = Store::default();
+        let memory = Memory::new(&mut store, MemoryType::new(1, None, false)).unwrap();
+        let prime = modulus_as_limbs::<vesta::Base>().unwrap();
+        let mut memory = SafeMemory::new(memory, 8, prime);
+        memory.set_free_pos(&store, 8).unwrap();
+
+        let values = [
+            vesta::Base::ZERO,
+            vesta::Base::from(42),
+            -vesta::Base::from(42),
+            vesta::Base::from(u64::MAX),
+            vesta::Base::random(rand::thread_rng()),
+        ];
+        for value in values {
+            let ptr = memory.alloc_fr(&store).unwrap() as usize;
+            memory.write_fr(&store, ptr, value).unwrap();
+            assert_eq!(memory.read_fr::<vesta::Base>(&store, ptr).unwrap(), value);
+        }
+
+        // Short elements only take the first 8 bytes.
+        let ptr = memory.alloc_fr(&store).unwrap() as usize;
+        memory.write_fr(&store, ptr, -vesta::Base::ONE).unwrap();
+        assert_eq!(memory.read_u32(&store, ptr).unwrap(), u32::MAX);
+        assert_eq!(memory.read_u32(&store, ptr + 4).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_read_fr_montgomery() {
+        let mut store = Store::default();
+        let memory = Memory::new(&mut store, MemoryType::new(1, None, false)).unwrap();
+        let prime = modulus_as_limbs::<vesta::Base>().unwrap();
+        let mut memory = SafeMemory::new(memory, 8, prime);
+        memory.set_free_pos(&store, 8).unwrap();
+
+        // 42 * 2^256 in Montgomery form reads as 42.
+        let value = vesta::Base::from(42);
+        let montgomery = value * vesta::Base::from(2).pow_vartime([256]);
+        let ptr = memory.alloc_fr(&store).unwrap() as usize;
+        memory.write_fr(&store, ptr, montgomery).unwrap();
+        memory.write_u32(&store, ptr + 4, 0xC000_0000).unwrap();
+        assert_eq!(memory.read_fr::<vesta::Base>(&store, ptr).unwrap(), value);
+    }
+
+    #[test]
+    fn test_out_of_bounds() {
+        let mut store = Store::default();
+        let memory = Memory::new(&mut store, MemoryType::new(1, None, false)).unwrap();
+        let prime = modulus_as_limbs::<vesta::Base>().unwrap();
+        let mut memory = SafeMemory::new(memory, 8, prime);
+
+        let end = memory.view(&store).data_size() as usize;
+        assert!(matches!(
+            memory.read_u32(&store, end - 2),
+            Err(WitnessCalculatorError::MemoryAccess(_))
+        ));
+        assert!(matches!(
+            memory.write_u32(&store, end, 0),
+            Err(WitnessCalculatorError::MemoryAccess(_))
+        ));
+
+        // Allocations past the end of the address space are rejected.
+        memory.set_free_pos(&store, u32::MAX - 4).unwrap();
+        assert!(matches!(
+            memory.alloc_u32(&store),
+            Err(WitnessCalculatorError::MemoryAccess(_))
+        ));
+        assert!(matches!(
+            memory.alloc_fr(&store),
+            Err(WitnessCalculatorError::MemoryAccess(_))
+        ));
+        assert_eq!(memory.free_pos(&store).unwrap(), u32::MAX - 4);
 
-        U256::from_le_slice(&buf[ptr..])
+        assert!(matches!(
+            memory.read_big(&store, end - 16),
+            Err(WitnessCalculatorError::MemoryAccess(_))
+        ));
+        assert!(matches!(
+            memory.write_big(&store, end - 16, &[0; 8]),
+            Err(WitnessCalculatorError::MemoryAccess(_))
+        ));
+        assert!(matches!(
+            memory.read_fr::<vesta::Base>(&store, usize::MAX - 4),
+            Err(WitnessCalculatorError::MemoryAccess(_))
+        ));
     }
 }
@@ -9,11 +9,24 @@
 //!
 //! This module is comprised of several submodules, each responsible for different aspects of the WASM interaction:
 //! - `witness_calculator`: Manages the WASM instance and performs witness calculation. It abstracts the interaction
-//!   with the WASM module, providing a high-level interface for witness generation.
+//!   with the WASM module, providing a high-level interface for witness generation on any engine.
+//! - `wasmer_calculator`: Runs the WASM by compiling it to native code with wasmer, the default engine. Available
+//!   with the `wasmer` feature, enabled by default.
 //! - `memory`: Handles memory operations and safety within the WASM environment, ensuring proper allocation, read, and
 //!   write operations on the WASM memory.
+//! - `pool`: Manages a pool of witness calculators sharing the same compiled WASM module, to calculate witnesses
+//!   concurrently.
+//! - `cache`: Stores calculated witnesses in memory or on disk, keyed by a hash of the WASM module and the inputs, so
+//!   that identical calculations are not run again. Available with the `cache` feature.
+//! - `profile`: Instruments the WASM to count the calls and instructions of each function, and reports them by
+//!   circom template.
+//! - `native`: Calculates witnesses by running the native witness generator circom outputs with `--c`, rather than
+//!   WASM.
+//! - `wasmi_calculator`: Runs the WASM by interpreting it with wasmi rather than compiling it with wasmer, for targets
+//!   that forbid generating code at runtime. Available with the `wasmi` feature.
 //! - `circom`: Provides traits and implementations specific to Circom, supporting both Circom versions 1 and 2. It
-//!   includes functionalities such as initialization, memory access, and version-specific operations.
+//!   includes functionalities such as initialization, memory access, and version-specific operations. The `Circom`
+//!   trait is independent of the engine running the WASM, and is implemented by each engine.
 //!
 //! Additionally, this module defines utility functions for hashing and other common operations used across the Circom
 //! Scotia library.
@@ -24,15 +37,57 @@
 //! - Support for both Circom 1 and Circom 2.
 //! - Utility functions for hashing and other operations.
 mod witness_calculator;
-pub use witness_calculator::WitnessCalculator;
+#[cfg(all(test, feature = "bundle"))]
+pub(crate) use witness_calculator::tests::Goldilocks;
+pub use witness_calculator::{LogSink, WitnessCalculator};
 
+#[cfg(feature = "wasmer")]
+mod wasmer_calculator;
+#[cfg(feature = "wasmer")]
+pub use wasmer_calculator::Wasm;
+
+mod pool;
+pub use pool::{PooledWitnessCalculator, WitnessCalculatorPool};
+
+#[cfg(all(feature = "wasmer", not(target_arch = "wasm32")))]
+mod profile;
+#[cfg(all(feature = "wasmer", not(target_arch = "wasm32")))]
+pub use profile::{Profile, Profiler, TemplateProfile};
+
+#[cfg(feature = "cache")]
+mod cache;
+#[cfg(feature = "cache")]
+pub use cache::{WitnessCache, WitnessKey};
+
+mod native;
+pub use native::NativeWitnessCalculator;
+
+#[cfg(feature = "wasmi")]
+mod wasmi_calculator;
+#[cfg(feature = "wasmi")]
+pub use wasmi_calculator::{WasmiInstance, WasmiWitnessCalculator};
+
+#[cfg(feature = "wasmer")]
 mod memory;
+#[cfg(feature = "wasmer")]
 pub(super) use memory::SafeMemory;
 
 mod circom;
 mod error;
+use error::Result;
+pub use error::WitnessCalculatorError;
+
+use circom::check_prime;
+pub use circom::Circom;
 
-pub(super) use circom::{Circom, Wasm};
+/// The engine witness calculators run on unless another one is given: wasmer, or wasmi when it is
+/// the only engine enabled.
+#[cfg(feature = "wasmer")]
+pub type DefaultEngine = Wasm;
+/// The engine witness calculators run on unless another one is given: wasmer, or wasmi when it is
+/// the only engine enabled.
+#[cfg(all(feature = "wasmi", not(feature = "wasmer")))]
+pub type DefaultEngine = WasmiInstance;
 
 use fnv::FnvHasher;
 use std::hash::Hasher;
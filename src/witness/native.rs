@@ -0,0 +1,209 @@
+// Copyright (c) Lurk Lab
+// SPDX-License-Identifier: MIT
+//! # Native witness calculator module
+//!
+//! The `native` module provides [`NativeWitnessCalculator`], which calculates witnesses by running the witness generator
+//! circom outputs with `--c`, once compiled. Native witness generators are much faster than WebAssembly ones on large
+//! circuits.
+//!
+//! A native witness generator is run as `<binary> <input.json> <output.wtns>`: the inputs are written to a JSON file, and
+//! the witness is read back from the `.wtns` file it writes.
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use ff::{PrimeField, PrimeFieldBits};
+use log::{info, warn};
+
+use super::{Result, WitnessCalculatorError::GeneratorFailed};
+use crate::r1cs::CircomInput;
+use crate::reader::{inputs_as_json, load_witness_from_bin_file};
+
+/// Number of calculations started by this process, which tells their working directories apart.
+static CALCULATIONS: AtomicUsize = AtomicUsize::new(0);
+
+/// A struct for calculating witnesses in Circom circuits, running the native witness generator of the circuit.
+#[derive(Clone, Debug)]
+pub struct NativeWitnessCalculator {
+    /// The path to the compiled witness generator.
+    binary: PathBuf,
+    /// The directory under which the input and witness files of each calculation are written.
+    work_dir: PathBuf,
+}
+
+impl NativeWitnessCalculator {
+    /// Constructs a new [`NativeWitnessCalculator`] running the given witness generator.
+    ///
+    /// Input and witness files are written under the temporary directory of the system, see
+    /// [`NativeWitnessCalculator::set_work_dir`].
+    ///
+    /// # Arguments
+    ///
+    /// * `binary` - The path to the witness generator, compiled from the C++ code circom outputs with `--c`.
+    pub fn new(binary: impl Into<PathBuf>) -> Self {
+        Self {
+            binary: binary.into(),
+            work_dir: std::env::temp_dir(),
+        }
+    }
+
+    /// Sets the directory under which the input and witness files of each calculation are written. Each calculation
+    /// uses its own subdirectory, which is removed once it is done.
+    pub fn set_work_dir(&mut self, work_dir: impl Into<PathBuf>) {
+        self.work_dir = work_dir.into();
+    }
+
+    /// Calculates the witness for a given set of Circom inputs.
+    ///
+    /// # Arguments
+    ///
+    /// * `inputs` - A vector of Circom inputs for the computation.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the witness generator cannot be run or does not exit successfully, or if the witness it
+    /// writes cannot be read as elements of the field `F`.
+    pub fn calculate_witness<F: PrimeFieldBits>(
+        &self,
+        inputs: Vec<CircomInput<F>>,
+    ) -> Result<Vec<F>> {
+        self.calculate_witness_from_json(&inputs_as_json(&inputs))
+    }
+
+    /// Calculates the witness for the inputs held in a JSON object, mapping the name of each input signal to its value.
+    ///
+    /// # Arguments
+    ///
+    /// * `input_json` - The inputs of the circuit, in the JSON format circom witness generators read.
+    ///
+    /// # Errors
+    ///
+    /// See [`NativeWitnessCalculator::calculate_witness`].
+    pub fn calculate_witness_from_json<F: PrimeField>(&self, input_json: &str) -> Result<Vec<F>> {
+        let dir = self.work_dir.join(format!(
+            "circom-scotia-{}-{}",
+            std::process::id(),
+            CALCULATIONS.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::create_dir_all(&dir)?;
+
+        let witness = self.run(&dir, input_json);
+
+        if let Err(err) = fs::remove_dir_all(&dir) {
+            warn!("could not clean up {}: {err}", dir.display());
+        }
+
+        witness
+    }
+
+    /// Runs the witness generator with its input and witness files in `dir`.
+    fn run<F: PrimeField>(&self, dir: &Path, input_json: &str) -> Result<Vec<F>> {
+        let input = dir.join("input.json");
+        let witness = dir.join("witness.wtns");
+        fs::write(&input, input_json)?;
+
+        let output = Command::new(&self.binary)
+            .arg(&input)
+            .arg(&witness)
+            .output()?;
+        if !output.status.success() {
+            return Err(GeneratorFailed {
+                status: output.status,
+                output: printed(&output),
+            });
+        }
+
+        let printed = printed(&output);
+        if !printed.is_empty() {
+            info!("{printed}");
+        }
+
+        Ok(load_witness_from_bin_file(&witness)?)
+    }
+}
+
+/// Returns what a witness generator printed, on its standard error then on its standard output.
+fn printed(output: &Output) -> String {
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    [stderr.trim(), stdout.trim()]
+        .into_iter()
+        .filter(|printed| !printed.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::os::unix::fs::PermissionsExt;
+
+    use ff::Field;
+    use pasta_curves::{pallas, vesta};
+
+    use super::*;
+    use crate::reader::tests::wtns_bytes;
+    use crate::witness::WitnessCalculatorError;
+
+    /// Writes an executable shell script standing in for a witness generator, in a directory of its own.
+    fn stub(name: &str, script: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("circom-scotia-stub-{}-{name}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let binary = dir.join(name);
+        fs::write(&binary, format!("#!/bin/sh\n{script}\n")).unwrap();
+        fs::set_permissions(&binary, fs::Permissions::from_mode(0o755)).unwrap();
+        binary
+    }
+
+    #[test]
+    fn test_calculate_witness() {
+        let expected = [vesta::Base::ONE, vesta::Base::from(42), -vesta::Base::ONE];
+        let binary = stub(
+            "adder",
+            r#"grep -q '"a":\["2"\],"b":\["40"\]' "$1" || exit 3
+cp "$(dirname "$0")/adder.wtns" "$2""#,
+        );
+        fs::write(binary.with_extension("wtns"), wtns_bytes(&expected)).unwrap();
+
+        let mut wtns = NativeWitnessCalculator::new(&binary);
+        wtns.set_work_dir(binary.parent().unwrap());
+        let inputs = vec![
+            CircomInput::new("a".into(), vec![vesta::Base::from(2)]),
+            CircomInput::new("b".into(), vec![vesta::Base::from(40)]),
+        ];
+        let witness = wtns.calculate_witness(inputs.clone()).unwrap();
+        assert_eq!(witness, expected);
+
+        // The working directory of the calculation is removed.
+        assert_eq!(fs::read_dir(binary.parent().unwrap()).unwrap().count(), 2);
+
+        // The witness is read for the field of the inputs.
+        let err = wtns
+            .calculate_witness_from_json::<pallas::Base>(&inputs_as_json(&inputs))
+            .unwrap_err();
+        assert!(matches!(err, WitnessCalculatorError::ReadWitness(_)));
+
+        fs::remove_dir_all(binary.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_generator_failed() {
+        let binary = stub(
+            "failing",
+            "echo 'Failed assert in template Adder line 5'\nexit 1",
+        );
+        let wtns = NativeWitnessCalculator::new(&binary);
+        let err = wtns.calculate_witness::<vesta::Base>(vec![]).unwrap_err();
+        assert!(matches!(
+            err,
+            WitnessCalculatorError::GeneratorFailed { status, output }
+                if status.code() == Some(1) && output == "Failed assert in template Adder line 5"
+        ));
+        fs::remove_dir_all(binary.parent().unwrap()).unwrap();
+
+        let wtns = NativeWitnessCalculator::new("circom/mock/missing");
+        let err = wtns.calculate_witness::<vesta::Base>(vec![]).unwrap_err();
+        assert!(matches!(err, WitnessCalculatorError::Io(_)));
+    }
+}
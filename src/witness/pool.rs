@@ -0,0 +1,422 @@
+// Copyright (c) Lurk Lab
+// SPDX-License-Identifier: MIT
+//! # Witness calculator pool
+//!
+//! The `pool` module provides [`WitnessCalculatorPool`], which lets many threads calculate witnesses
+//! for the same circuit concurrently. The WebAssembly module is compiled once, and each
+//! [`WitnessCalculator`] of the pool instantiates it in its own store. Pools run on any engine
+//! implementing [`Circom`], the [`DefaultEngine`] unless another one is given.
+//!
+//! Calculators are checked out with [`WitnessCalculatorPool::get`] and returned to the pool when the
+//! [`PooledWitnessCalculator`] guard is dropped. With the `async` feature, tasks can wait for a free calculator
+//! with [`WitnessCalculatorPool::acquire`] without blocking their executor.
+use super::Result;
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::path::Path;
+#[cfg(feature = "async")]
+use std::sync::Arc;
+use std::sync::{Condvar, Mutex, MutexGuard, PoisonError};
+#[cfg(feature = "async")]
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+#[cfg(feature = "wasmer")]
+use wasmer::{Module, Store};
+
+#[cfg(feature = "wasmer")]
+use super::Wasm;
+use super::{Circom, DefaultEngine, LogSink, WitnessCalculator};
+use crate::r1cs::SymbolTable;
+
+/// A pool of up to `size` [`WitnessCalculator`]s instantiated from the same compiled module.
+///
+/// Calculators are instantiated lazily, when all the instantiated ones are checked out, or up front
+/// with [`WitnessCalculatorPool::fill`].
+pub struct WitnessCalculatorPool<C: Circom = DefaultEngine> {
+    module: C::Module,
+    /// The hash of the WebAssembly module, if the pool was loaded from a file.
+    #[cfg(feature = "cache")]
+    module_hash: Option<[u8; 32]>,
+    size: usize,
+    state: Mutex<PoolState<C>>,
+    returned: Condvar,
+    /// One permit per calculator, handed out to tasks waiting for a calculator asynchronously.
+    #[cfg(feature = "async")]
+    permits: Arc<Semaphore>,
+}
+
+/// Calculators waiting to be checked out, the number of calculators instantiated so far, and the
+/// settings applied to every calculator of the pool.
+struct PoolState<C: Circom> {
+    idle: Vec<WitnessCalculator<C>>,
+    instantiated: usize,
+    log_sink: LogSink,
+    symbols: Option<SymbolTable>,
+    /// Incremented whenever the settings change, so that calculators checked out at the time get
+    /// them when returned.
+    generation: u64,
+}
+
+impl<C: Circom> PoolState<C> {
+    /// Applies the settings of the pool to `calculator`.
+    fn configure(&self, calculator: &mut WitnessCalculator<C>) {
+        calculator.set_log_sink(self.log_sink.clone());
+        if let Some(symbols) = &self.symbols {
+            calculator.set_symbols(symbols.clone());
+        }
+    }
+}
+
+impl<C: Circom> fmt::Debug for WitnessCalculatorPool<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WitnessCalculatorPool")
+            .field("module", &self.module)
+            .field("size", &self.size)
+            .field("instantiated", &self.instantiated())
+            .finish()
+    }
+}
+
+impl WitnessCalculatorPool {
+    /// Constructs a [`WitnessCalculatorPool`] of up to `size` calculators from a file containing a
+    /// WebAssembly module, running on the [`DefaultEngine`].
+    ///
+    /// The module is compiled and instantiated once, so that an invalid module is reported here
+    /// rather than when checking out a calculator.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the WebAssembly module cannot be loaded or instantiated.
+    pub fn new(path: impl AsRef<Path>, size: usize) -> Result<Self> {
+        Self::load(path, size)
+    }
+}
+
+#[cfg(feature = "wasmer")]
+impl WitnessCalculatorPool<Wasm> {
+    /// Constructs a [`WitnessCalculatorPool`] of up to `size` calculators from a WebAssembly module
+    /// compiled with the engine of `store`.
+    ///
+    /// The first calculator is instantiated in `store`, the others in new stores sharing its engine.
+    /// As with [`WitnessCalculator::from_module`], only the globals `module` exports are restored
+    /// after a failed calculation.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the WebAssembly module cannot be instantiated.
+    pub fn from_module(module: Module, store: Store, size: usize) -> Result<Self> {
+        let engine = store.engine().clone();
+        let calculator = WitnessCalculator::from_module(module.clone(), store)?;
+        Ok(Self::with_calculator((module, engine), calculator, size))
+    }
+}
+
+impl<C: Circom> WitnessCalculatorPool<C> {
+    /// Constructs a [`WitnessCalculatorPool`] of up to `size` calculators running on the engine `C`
+    /// from a file containing a WebAssembly module.
+    ///
+    /// See [`WitnessCalculatorPool::new`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the WebAssembly module cannot be loaded or instantiated.
+    pub fn load(path: impl AsRef<Path>, size: usize) -> Result<Self> {
+        let module = C::compile(path.as_ref())?;
+        let calculator = WitnessCalculator::instantiate(&module)?;
+        #[cfg(feature = "cache")]
+        let calculator = {
+            let mut calculator = calculator;
+            calculator.module_hash = Some(super::cache::hash_module(path)?);
+            calculator
+        };
+        Ok(Self::with_calculator(module, calculator, size))
+    }
+
+    /// Constructs a [`WitnessCalculatorPool`] of up to `size` calculators instantiated from
+    /// `module`, starting with `calculator`.
+    fn with_calculator(module: C::Module, calculator: WitnessCalculator<C>, size: usize) -> Self {
+        let size = size.max(1);
+
+        Self {
+            module,
+            #[cfg(feature = "cache")]
+            module_hash: calculator.module_hash,
+            size,
+            state: Mutex::new(PoolState {
+                idle: vec![calculator],
+                instantiated: 1,
+                log_sink: LogSink::default(),
+                symbols: None,
+                generation: 0,
+            }),
+            returned: Condvar::new(),
+            #[cfg(feature = "async")]
+            permits: Arc::new(Semaphore::new(size)),
+        }
+    }
+
+    /// Returns the maximum number of calculators of the pool.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Returns the number of calculators instantiated so far.
+    pub fn instantiated(&self) -> usize {
+        self.state().instantiated
+    }
+
+    /// Sets where the messages printed through circom's `log()` are sent by every calculator of the
+    /// pool, including the ones instantiated later.
+    ///
+    /// Calculators checked out at the time are updated when returned to the pool.
+    pub fn set_log_sink(&self, sink: LogSink) {
+        self.update(|state| state.log_sink = sink);
+    }
+
+    /// Sets the symbol table of the circuit on every calculator of the pool, including the ones
+    /// instantiated later. See [`WitnessCalculator::set_symbols`].
+    ///
+    /// Calculators checked out at the time are updated when returned to the pool.
+    pub fn set_symbols(&self, symbols: SymbolTable) {
+        self.update(|state| state.symbols = Some(symbols));
+    }
+
+    /// Instantiates the calculators that have not been instantiated yet.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the WebAssembly module cannot be instantiated.
+    pub fn fill(&self) -> Result<()> {
+        while self.reserve() {
+            let (calculator, generation) = self.instantiate()?;
+            self.release(calculator, generation);
+        }
+
+        Ok(())
+    }
+
+    /// Checks out a calculator, which is returned to the pool when the guard is dropped.
+    ///
+    /// An idle calculator is handed out if there is one, otherwise a new one is instantiated if the
+    /// pool is not full yet. If it is, this blocks until another thread returns a calculator.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a new calculator cannot be instantiated.
+    pub fn get(&self) -> Result<PooledWitnessCalculator<'_, C>> {
+        let mut state = self.state();
+        loop {
+            if let Some(calculator) = state.idle.pop() {
+                return Ok(PooledWitnessCalculator {
+                    pool: self,
+                    calculator: Some(calculator),
+                    generation: state.generation,
+                });
+            }
+            if state.instantiated < self.size {
+                state.instantiated += 1;
+                drop(state);
+                let (calculator, generation) = self.instantiate()?;
+                return Ok(PooledWitnessCalculator {
+                    pool: self,
+                    calculator: Some(calculator),
+                    generation,
+                });
+            }
+            state = self
+                .returned
+                .wait(state)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+    }
+
+    /// Waits until one of the calculators of the pool can be reserved for a task, without blocking
+    /// the thread.
+    ///
+    /// Holders of the returned permit are at most as many as the calculators of the pool, so that
+    /// [`WitnessCalculatorPool::get`] only blocks them while calculators are checked out by threads
+    /// that did not acquire a permit. The calculator is reserved until the permit is dropped.
+    #[cfg(feature = "async")]
+    pub async fn acquire(&self) -> OwnedSemaphorePermit {
+        Arc::clone(&self.permits)
+            .acquire_owned()
+            .await
+            .expect("the semaphore of the pool is never closed")
+    }
+
+    /// The state only holds idle calculators and a counter, which remain consistent even if a
+    /// thread panicked while holding the lock.
+    fn state(&self) -> MutexGuard<'_, PoolState<C>> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Changes the settings of the pool and applies them to the idle calculators.
+    fn update(&self, change: impl FnOnce(&mut PoolState<C>)) {
+        let mut state = self.state();
+        change(&mut state);
+        state.generation += 1;
+        let mut idle = std::mem::take(&mut state.idle);
+        for calculator in &mut idle {
+            state.configure(calculator);
+        }
+        state.idle = idle;
+    }
+
+    /// Reserves a slot for a new calculator, returning `false` if the pool is full.
+    fn reserve(&self) -> bool {
+        let mut state = self.state();
+        if state.instantiated < self.size {
+            state.instantiated += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Instantiates a calculator for a reserved slot, freeing the slot on failure.
+    ///
+    /// Returns the calculator along with the generation of the settings applied to it.
+    fn instantiate(&self) -> Result<(WitnessCalculator<C>, u64)> {
+        let mut calculator = WitnessCalculator::instantiate(&self.module).map_err(|err| {
+            self.discard();
+            err
+        })?;
+        #[cfg(feature = "cache")]
+        {
+            calculator.module_hash = self.module_hash;
+        }
+        let state = self.state();
+        state.configure(&mut calculator);
+        Ok((calculator, state.generation))
+    }
+
+    /// Returns a calculator to the pool, applying the settings changed since it was configured,
+    /// and wakes up a thread waiting for one.
+    fn release(&self, mut calculator: WitnessCalculator<C>, generation: u64) {
+        let mut state = self.state();
+        if state.generation != generation {
+            state.configure(&mut calculator);
+        }
+        state.idle.push(calculator);
+        drop(state);
+        self.returned.notify_one();
+    }
+
+    /// Frees the slot of a calculator that will not be returned, waking up a thread waiting for one
+    /// so that it instantiates a replacement.
+    fn discard(&self) {
+        self.state().instantiated -= 1;
+        self.returned.notify_one();
+    }
+}
+
+/// A [`WitnessCalculator`] checked out from a [`WitnessCalculatorPool`], returned to the pool when
+/// dropped.
+#[derive(Debug)]
+pub struct PooledWitnessCalculator<'a, C: Circom = DefaultEngine> {
+    pool: &'a WitnessCalculatorPool<C>,
+    calculator: Option<WitnessCalculator<C>>,
+    /// The generation of the settings of the pool applied to the calculator.
+    generation: u64,
+}
+
+impl<C: Circom> Deref for PooledWitnessCalculator<'_, C> {
+    type Target = WitnessCalculator<C>;
+
+    fn deref(&self) -> &Self::Target {
+        self.calculator
+            .as_ref()
+            .expect("calculator is only taken on drop")
+    }
+}
+
+impl<C: Circom> DerefMut for PooledWitnessCalculator<'_, C> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.calculator
+            .as_mut()
+            .expect("calculator is only taken on drop")
+    }
+}
+
+impl<C: Circom> Drop for PooledWitnessCalculator<'_, C> {
+    fn drop(&mut self) {
+        if let Some(calculator) = self.calculator.take() {
+            // A calculator dropped while panicking may have been interrupted mid-calculation.
+            if std::thread::panicking() {
+                self.pool.discard();
+            } else {
+                self.pool.release(calculator, self.generation);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ff::Field;
+    use pasta_curves::vesta;
+
+    use super::*;
+    use crate::r1cs::CircomInput;
+
+    #[test]
+    fn test_pool_concurrent_witnesses() {
+        let pool = WitnessCalculatorPool::new("circom/sha256/circom_sha256.wasm", 2).unwrap();
+        assert_eq!(pool.instantiated(), 1);
+
+        let witnesses = std::thread::scope(|s| {
+            let handles = (0..4)
+                .map(|_| {
+                    s.spawn(|| {
+                        let inputs = vec![CircomInput::new(
+                            "arg_in".into(),
+                            vec![vesta::Base::ZERO; 2],
+                        )];
+                        pool.get().unwrap().calculate_witness(inputs, true).unwrap()
+                    })
+                })
+                .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap())
+                .collect::<Vec<_>>()
+        });
+
+        assert!(witnesses.windows(2).all(|w| w[0] == w[1]));
+        assert!(pool.instantiated() <= 2);
+    }
+
+    #[test]
+    fn test_pool_fill() {
+        let pool = WitnessCalculatorPool::new("circom/sha256/circom_sha256.wasm", 3).unwrap();
+        pool.fill().unwrap();
+        assert_eq!(pool.instantiated(), 3);
+
+        let first = pool.get().unwrap();
+        let second = pool.get().unwrap();
+        let third = pool.get().unwrap();
+        assert_eq!(pool.instantiated(), 3);
+        assert!(pool.state().idle.is_empty());
+
+        drop((first, second, third));
+        assert_eq!(pool.state().idle.len(), 3);
+    }
+
+    #[test]
+    fn test_pool_settings() {
+        let pool = WitnessCalculatorPool::new("circom/sha256/circom_sha256.wasm", 3).unwrap();
+        let is_collected = |calculator: &mut WitnessCalculator| {
+            let messages = calculator.instance.messages(&mut calculator.store);
+            matches!(messages.sink, LogSink::Collect)
+        };
+
+        // One calculator checked out, one idle, and one not instantiated yet.
+        let mut checked_out = pool.get().unwrap();
+        drop(pool.get().unwrap());
+        pool.set_log_sink(LogSink::Collect);
+        assert!(!is_collected(&mut checked_out));
+        drop(checked_out);
+
+        let mut calculators = (0..3).map(|_| pool.get().unwrap()).collect::<Vec<_>>();
+        assert!(calculators.iter_mut().all(|c| is_collected(c)));
+    }
+}
@@ -0,0 +1,348 @@
+// Copyright (c) Lurk Lab
+// SPDX-License-Identifier: MIT
+//! # Profile module
+//!
+//! The `profile` module provides [`Profiler`], a wasmer middleware counting the calls and the instructions executed by
+//! each function of a WebAssembly module, and [`Profile`], the report built from these counters.
+//!
+//! Circom 2 compiles each instantiation of a template to a `<Template>_<id>_create` and a `<Template>_<id>_run`
+//! function, so that the counters of these functions tell which templates dominate the time spent calculating
+//! witnesses. Profiling is enabled with [`super::WitnessCalculator::with_profiling`], and reports are returned along
+//! with the witness by [`super::WitnessCalculator::calculate_witness_with_profile`].
+//!
+//! Counters are kept in mutable globals the middleware adds to the module and exports, one pair per function, which
+//! are incremented by the code instrumenting each function. Instructions are counted by basic block: the count of a
+//! block is added to the counter of its function before the operator ending the block.
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Mutex, PoisonError};
+
+use wasmer::wasmparser::Operator;
+use wasmer::{
+    ExportIndex, FunctionMiddleware, GlobalInit, GlobalType, LocalFunctionIndex, MiddlewareError,
+    MiddlewareReaderState, ModuleMiddleware, Mutability, Type,
+};
+use wasmer_types::{GlobalIndex, ModuleInfo};
+
+/// Prefix of the exported globals counting the calls of each function.
+const CALLS_PREFIX: &str = "__circom_scotia_calls_";
+/// Prefix of the exported globals counting the instructions executed by each function.
+const INSTRUCTIONS_PREFIX: &str = "__circom_scotia_instructions_";
+
+/// A wasmer middleware instrumenting every function of a module to count its calls and the instructions it executes.
+///
+/// A [`Profiler`] instruments a single module: it records the names and the counters of the functions of the module it
+/// is applied to.
+#[derive(Debug, Default)]
+pub struct Profiler {
+    /// Counters of the local functions of the module, set once the module is transformed.
+    functions: Mutex<Option<Vec<FunctionCounters>>>,
+}
+
+/// Name of a local function and the globals counting its calls and instructions.
+#[derive(Clone, Debug)]
+struct FunctionCounters {
+    name: String,
+    calls: GlobalIndex,
+    instructions: GlobalIndex,
+}
+
+impl Profiler {
+    /// Returns the names of the local functions of the module, along with the names of the exported globals counting
+    /// their calls and instructions, or an empty list if no module was compiled with the profiler yet.
+    pub(crate) fn counters(&self) -> Vec<(String, String, String)> {
+        self.functions
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .flatten()
+            .enumerate()
+            .map(|(i, function)| {
+                (
+                    function.name.clone(),
+                    format!("{CALLS_PREFIX}{i}"),
+                    format!("{INSTRUCTIONS_PREFIX}{i}"),
+                )
+            })
+            .collect()
+    }
+
+    /// Adds a mutable `i64` global to the module, initialized to zero and exported as `name`.
+    fn add_counter(module_info: &mut ModuleInfo, name: String) -> GlobalIndex {
+        let index = module_info
+            .globals
+            .push(GlobalType::new(Type::I64, Mutability::Var));
+        module_info
+            .global_initializers
+            .push(GlobalInit::I64Const(0));
+        module_info.exports.insert(name, ExportIndex::Global(index));
+        index
+    }
+}
+
+impl ModuleMiddleware for Profiler {
+    fn generate_function_middleware(
+        &self,
+        local_function_index: LocalFunctionIndex,
+    ) -> Box<dyn FunctionMiddleware> {
+        let functions = self
+            .functions
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let counters = functions
+            .as_ref()
+            .and_then(|functions| functions.get(local_function_index.as_u32() as usize))
+            .expect("Profiler: module info is transformed before functions are instrumented")
+            .clone();
+
+        Box::new(FunctionProfiler {
+            counters,
+            entered: false,
+            accumulated: 0,
+        })
+    }
+
+    fn transform_module_info(&self, module_info: &mut ModuleInfo) -> Result<(), MiddlewareError> {
+        let mut functions = self
+            .functions
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if functions.is_some() {
+            return Err(MiddlewareError::new(
+                "Profiler",
+                "a profiler can only instrument a single module",
+            ));
+        }
+
+        let local_functions = module_info.functions.len() - module_info.num_imported_functions;
+        let counters = (0..local_functions)
+            .map(|i| {
+                let index = module_info.func_index(LocalFunctionIndex::from_u32(i as u32));
+                let name = module_info
+                    .function_names
+                    .get(&index)
+                    .cloned()
+                    .unwrap_or_else(|| format!("function_{}", index.as_u32()));
+                FunctionCounters {
+                    name,
+                    calls: Self::add_counter(module_info, format!("{CALLS_PREFIX}{i}")),
+                    instructions: Self::add_counter(
+                        module_info,
+                        format!("{INSTRUCTIONS_PREFIX}{i}"),
+                    ),
+                }
+            })
+            .collect();
+        *functions = Some(counters);
+
+        Ok(())
+    }
+}
+
+/// The middleware instrumenting a single function.
+#[derive(Debug)]
+struct FunctionProfiler {
+    counters: FunctionCounters,
+    /// Whether the first operator of the function, before which calls are counted, was fed.
+    entered: bool,
+    /// Instructions of the current basic block not added to the counter yet.
+    accumulated: u64,
+}
+
+impl FunctionProfiler {
+    /// Pushes the operators adding `value` to the counter held in `global`.
+    fn increment<'a>(state: &mut MiddlewareReaderState<'a>, global: GlobalIndex, value: u64) {
+        state.extend([
+            Operator::GlobalGet {
+                global_index: global.as_u32(),
+            },
+            Operator::I64Const {
+                value: value as i64,
+            },
+            Operator::I64Add,
+            Operator::GlobalSet {
+                global_index: global.as_u32(),
+            },
+        ]);
+    }
+}
+
+impl FunctionMiddleware for FunctionProfiler {
+    fn feed<'a>(
+        &mut self,
+        operator: Operator<'a>,
+        state: &mut MiddlewareReaderState<'a>,
+    ) -> Result<(), MiddlewareError> {
+        if !self.entered {
+            self.entered = true;
+            Self::increment(state, self.counters.calls, 1);
+        }
+
+        self.accumulated += 1;
+        // Operators that may transfer control end the basic block, whose instructions are then all executed.
+        if matches!(
+            operator,
+            Operator::Loop { .. }
+                | Operator::End
+                | Operator::If { .. }
+                | Operator::Else
+                | Operator::Br { .. }
+                | Operator::BrTable { .. }
+                | Operator::BrIf { .. }
+                | Operator::Call { .. }
+                | Operator::CallIndirect { .. }
+                | Operator::Return
+                | Operator::Unreachable
+        ) {
+            Self::increment(state, self.counters.instructions, self.accumulated);
+            self.accumulated = 0;
+        }
+
+        state.push_operator(operator);
+        Ok(())
+    }
+}
+
+/// Calls and instructions of the functions of a template, or of a function that is not part of a template.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TemplateProfile {
+    /// The name of the template, or of the function.
+    pub name: String,
+    /// The number of components of the template that were run, or the number of calls of the function.
+    pub calls: u64,
+    /// The number of WebAssembly instructions executed by the functions of the template, excluding the instructions
+    /// of the functions they call.
+    pub instructions: u64,
+}
+
+/// A report of the calls and instructions of the templates of a circuit, sorted by decreasing number of instructions.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Profile {
+    pub templates: Vec<TemplateProfile>,
+}
+
+impl Profile {
+    /// Builds a report from the calls and instructions counted for each function, grouping the functions of each
+    /// template. Templates and functions that were not called are left out.
+    pub(crate) fn new(functions: impl IntoIterator<Item = (String, u64, u64)>) -> Self {
+        let mut templates = HashMap::<String, TemplateProfile>::new();
+        for (name, calls, instructions) in functions {
+            if calls == 0 && instructions == 0 {
+                continue;
+            }
+
+            let (template, calls) = match template_name(&name) {
+                Some((template, "run")) => (template.to_string(), calls),
+                Some((template, _)) => (template.to_string(), 0),
+                None => (name, calls),
+            };
+            let profile = templates
+                .entry(template.clone())
+                .or_insert_with(|| TemplateProfile {
+                    name: template,
+                    ..TemplateProfile::default()
+                });
+            profile.calls += calls;
+            profile.instructions += instructions;
+        }
+
+        let mut templates = templates.into_values().collect::<Vec<_>>();
+        templates.sort_by(|a, b| {
+            b.instructions
+                .cmp(&a.instructions)
+                .then_with(|| a.name.cmp(&b.name))
+        });
+        Self { templates }
+    }
+
+    /// Returns the total number of instructions executed.
+    pub fn instructions(&self) -> u64 {
+        self.templates
+            .iter()
+            .map(|template| template.instructions)
+            .sum()
+    }
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let total = self.instructions().max(1) as f64;
+        let width = self
+            .templates
+            .iter()
+            .map(|template| template.name.len())
+            .chain([8])
+            .max()
+            .unwrap_or_default();
+        writeln!(
+            f,
+            "{:<width$} {:>12} {:>16} {:>7}",
+            "template", "calls", "instructions", "share"
+        )?;
+        for template in &self.templates {
+            writeln!(
+                f,
+                "{:<width$} {:>12} {:>16} {:>6.2}%",
+                template.name,
+                template.calls,
+                template.instructions,
+                template.instructions as f64 * 100.0 / total
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Splits the name of a function circom generates for a template, `<Template>_<id>_create` or `<Template>_<id>_run`,
+/// into the name of the template and the kind of function.
+fn template_name(function: &str) -> Option<(&str, &str)> {
+    let (rest, kind) = function.rsplit_once('_')?;
+    if kind != "run" && kind != "create" {
+        return None;
+    }
+    let (template, id) = rest.rsplit_once('_')?;
+    if template.is_empty() || id.is_empty() || !id.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    Some((template, kind))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profile() {
+        let profile = Profile::new([
+            ("Num2Bits_1_create".to_string(), 3, 30),
+            ("Num2Bits_1_run".to_string(), 3, 600),
+            ("Num2Bits_7_run".to_string(), 1, 200),
+            ("Fr_mul".to_string(), 50, 1000),
+            ("Unused_2_run".to_string(), 0, 0),
+            ("get_2_run_x".to_string(), 1, 5),
+        ]);
+
+        assert_eq!(
+            profile.templates,
+            [
+                TemplateProfile {
+                    name: "Fr_mul".into(),
+                    calls: 50,
+                    instructions: 1000
+                },
+                TemplateProfile {
+                    name: "Num2Bits".into(),
+                    calls: 4,
+                    instructions: 830
+                },
+                TemplateProfile {
+                    name: "get_2_run_x".into(),
+                    calls: 1,
+                    instructions: 5
+                },
+            ]
+        );
+        assert_eq!(profile.instructions(), 1835);
+        assert!(profile.to_string().contains("Num2Bits"));
+    }
+}
@@ -0,0 +1,891 @@
+// Copyright (c) 2021 Georgios Konstantopoulos
+// Copyright (c) Lurk Lab
+// SPDX-License-Identifier: MIT
+//! # Wasmer witness calculator module
+//!
+//! The `wasmer_calculator` module provides [`Wasm`], which runs circuits compiled with circom 1 or circom 2 by
+//! compiling their WebAssembly to native code with [wasmer](https://github.com/wasmerio/wasmer). It is the engine
+//! [`WitnessCalculator`]s run on by default.
+//!
+//! Wasmer instances get their memory through [`SafeMemory`], so that circom 1 circuits can be run and the shared
+//! read-write memory of circom 2 circuits can be accessed directly, see
+//! [`WitnessCalculator::set_direct_memory_access`]. After a failed calculation, the module is
+//! instantiated again in a new store sharing the engine of the previous one. The `runtime` submodule provides the
+//! callback hooks the WebAssembly imports.
+use std::path::Path;
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::Arc;
+
+use ff::PrimeFieldBits;
+#[cfg(not(target_arch = "wasm32"))]
+use wasmer::CompilerConfig;
+#[cfg(all(not(feature = "llvm"), not(target_arch = "wasm32")))]
+use wasmer::Cranelift;
+use wasmer::{
+    imports, wat2wasm, AsStoreMut, CompileError, Engine, Function, FunctionEnv, FunctionEnvMut,
+    Instance, IoCompileError, Memory, MemoryType, Module, RuntimeError, Store, Value, WasmError,
+};
+#[cfg(feature = "llvm")]
+use wasmer_compiler_llvm::LLVM;
+
+#[cfg(not(target_arch = "wasm32"))]
+use super::{Profile, Profiler};
+
+use super::{
+    fnv,
+    witness_calculator::Messages,
+    Circom, Result, SafeMemory, WitnessCalculator, WitnessCalculatorError,
+    WitnessCalculatorError::{MissingExports, UnexpectedReturn, VersionNotSupported},
+};
+use crate::r1cs::CircomInput;
+
+/// Represents a WebAssembly instance for Circom computations, run by wasmer.
+#[derive(Debug)]
+pub struct Wasm {
+    /// The module the instance was created from, instantiated again to reset the instance.
+    module: Module,
+    instance: Instance,
+    /// Memory of the instance, imported by circom 1 circuits and exported by circom 2 ones.
+    memory: SafeMemory,
+    runtime_env: FunctionEnv<runtime::RuntimeEnv>,
+    /// Version of circom the circuit was compiled with, once the instance is prepared.
+    version: u32,
+    /// Address of the shared read-write memory of circom 2 circuits in the instance memory.
+    shared_rw_memory: Option<usize>,
+    /// The middleware counting the calls and instructions of each function, if profiling is enabled.
+    #[cfg(not(target_arch = "wasm32"))]
+    profiler: Option<Arc<Profiler>>,
+}
+
+impl Circom for Wasm {
+    type Store = Store;
+    /// Wasmer modules are instantiated in stores sharing the engine that compiled them.
+    type Module = (Module, Engine);
+
+    fn compile(path: &Path) -> Result<Self::Module> {
+        let store = WitnessCalculator::new_store();
+        let module = Wasm::compile_in(&store, path)?;
+        Ok((module, store.engine().clone()))
+    }
+
+    fn instantiate((module, engine): &Self::Module) -> Result<(Self, Store)> {
+        Self::instantiate_in(module, Store::new(engine.clone()))
+    }
+
+    fn has_func(&self, name: &str) -> bool {
+        self.instance.exports.get_function(name).is_ok()
+    }
+
+    fn call(&self, store: &mut Store, name: &str, args: &[u32]) -> Result<Option<u32>> {
+        let params = args
+            .iter()
+            .map(|&arg| Value::I32(arg as i32))
+            .collect::<Vec<_>>();
+        let result = self.func(name)?.call(store, &params).map_err(|err| {
+            match err.downcast::<WitnessCalculatorError>() {
+                Ok(err) => err,
+                Err(err) => err.into(),
+            }
+        })?;
+
+        match *result {
+            [] => Ok(None),
+            [Value::I32(value)] => Ok(Some(value as u32)),
+            _ => Err(UnexpectedReturn(name.to_string())),
+        }
+    }
+
+    fn messages<'a>(&self, store: &'a mut Store) -> &'a mut Messages {
+        &mut self.runtime_env.as_mut(store).messages
+    }
+
+    fn prepare(&mut self, store: &mut Store, version: u32, n32: u32) -> Result<Vec<u32>> {
+        self.version = version;
+        self.memory = SafeMemory::new(self.memory.memory.clone(), n32 as usize, vec![]);
+        let prime = match version {
+            1 => {
+                let ptr = self.get_ptr_raw_prime(store)?;
+                self.memory.read_big(store, ptr as usize)?
+            }
+            2 => {
+                self.set_direct_memory_access(store, true)?;
+                self.get_raw_prime(store)?;
+                self.read_shared_rw_memory_limbs(store, n32)?
+            }
+            version => return Err(VersionNotSupported(version)),
+        };
+        self.memory.prime = prime.clone();
+        self.runtime_env.as_mut(store).memory = Some(self.memory.clone());
+
+        Ok(prime)
+    }
+
+    /// Instantiates the module again in a new store sharing the engine of the current one, and
+    /// prepares the new instance as the current one was. The messages of the current store carry
+    /// over, along with the [`Profiler`] instrumenting the module, if any.
+    fn reset(&mut self, store: &mut Store) -> Result<()> {
+        let (mut wasm, mut new_store) =
+            Self::instantiate_in(&self.module, Store::new(store.engine().clone()))?;
+        wasm.prepare(&mut new_store, self.version, self.memory.n32() as u32)?;
+        if self.shared_rw_memory.is_none() {
+            wasm.shared_rw_memory = None;
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            wasm.profiler = self.profiler.take();
+        }
+
+        let messages = std::mem::take(&mut self.runtime_env.as_mut(store).messages);
+        wasm.runtime_env.as_mut(&mut new_store).messages = messages;
+        *self = wasm;
+        *store = new_store;
+
+        Ok(())
+    }
+
+    /// Inputs are written to and the witness is read from the instance memory, through
+    /// [`SafeMemory`].
+    fn calculate_witness_circom1<F: PrimeFieldBits>(
+        &mut self,
+        store: &mut Store,
+        inputs: Vec<CircomInput<F>>,
+    ) -> Result<Vec<F>> {
+        let old_mem_free_pos = self.memory.free_pos(store)?;
+        let p_sig_offset = self.memory.alloc_u32(store)?;
+        let p_fr = self.memory.alloc_fr(store)?;
+
+        // allocate the inputs
+        for input in inputs {
+            let (msb, lsb) = fnv(&input.name);
+
+            self.get_signal_offset32(store, p_sig_offset, 0, msb, lsb)?;
+            let sig_offset = self.memory.read_u32(store, p_sig_offset as usize)?;
+
+            for (i, value) in input.value.into_iter().enumerate() {
+                self.memory.write_fr(store, p_fr as usize, value)?;
+                self.set_signal(store, 0, 0, sig_offset + i as u32, p_fr)?;
+            }
+        }
+
+        let mut w = Vec::new();
+
+        let n_vars = self.get_n_vars(store)?;
+        for i in 0..n_vars {
+            let ptr = self.get_ptr_witness(store, i)? as usize;
+            w.push(self.memory.read_fr(store, ptr)?);
+        }
+
+        self.memory.set_free_pos(store, old_mem_free_pos)?;
+
+        Ok(w)
+    }
+
+    /// The limbs are read directly from the instance memory when the circuit exports it, and limb by
+    /// limb through `readSharedRWMemory` otherwise.
+    fn read_shared_rw_memory_limbs(&self, store: &mut Store, n32: u32) -> Result<Vec<u32>> {
+        match self.shared_rw_memory {
+            Some(ptr) => self.memory.read_big(store, ptr),
+            None => (0..n32)
+                .map(|j| self.read_shared_rw_memory(store, j))
+                .collect(),
+        }
+    }
+
+    /// The limbs are written directly to the instance memory when the circuit exports it, and limb by
+    /// limb through `writeSharedRWMemory` otherwise.
+    fn write_shared_rw_memory_limbs(&self, store: &mut Store, limbs: &[u32]) -> Result<()> {
+        match self.shared_rw_memory {
+            Some(ptr) => self.memory.write_big(store, ptr, limbs),
+            None => limbs
+                .iter()
+                .enumerate()
+                .try_for_each(|(j, &limb)| self.write_shared_rw_memory(store, j as u32, limb)),
+        }
+    }
+}
+
+impl Wasm {
+    /// The shared read-write memory of circom 2 circuits is accessed directly when `direct` is set
+    /// and the circuit exports its memory and tells where it is.
+    fn set_direct_memory_access(&mut self, store: &mut Store, direct: bool) -> Result<()> {
+        self.shared_rw_memory = None;
+        if direct
+            && self.version == 2
+            && self.exported_memory().is_some()
+            && self.has_func("getSharedRWMemoryStart")
+        {
+            self.shared_rw_memory = Some(self.get_shared_rw_memory_start(store)? as usize);
+        }
+        Ok(())
+    }
+
+    /// Compiles the WebAssembly module in the file at `path`, in the binary or the text format, with
+    /// the engine of `store`.
+    fn compile_in(store: &Store, path: &Path) -> Result<Module> {
+        let bytes = std::fs::read(path).map_err(IoCompileError::Io)?;
+        let wasm = wat2wasm(&bytes).map_err(|err| {
+            IoCompileError::Compile(CompileError::Wasm(WasmError::Generic(err.to_string())))
+        })?;
+        Ok(Module::new(store, wasm).map_err(IoCompileError::Compile)?)
+    }
+
+    /// Instantiates `module` in `store`, with the memory circom 1 circuits import and the runtime
+    /// hooks.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the memory or the instance cannot be created.
+    pub fn instantiate_in(module: &Module, mut store: Store) -> Result<(Self, Store)> {
+        // Set up the memory
+        let memory = Memory::new(&mut store, MemoryType::new(2000, None, false))?;
+        let runtime_env = FunctionEnv::new(&mut store, runtime::RuntimeEnv::default());
+        let import_object = imports! {
+            "env" => {
+                "memory" => memory.clone(),
+            },
+            // Host function callbacks from the WASM
+            "runtime" => {
+                "error" => runtime::error(&mut store, &runtime_env),
+                "logSetSignal" => runtime::log_signal(&mut store),
+                "logGetSignal" => runtime::log_signal(&mut store),
+                "logFinishComponent" => runtime::log_component(&mut store),
+                "logStartComponent" => runtime::log_component(&mut store),
+                "log" => runtime::log_component(&mut store),
+                "exceptionHandler" => runtime::exception_handler(&mut store, &runtime_env),
+                "showSharedRWMemory" => runtime::show_memory(&mut store, &runtime_env),
+                "printErrorMessage" => runtime::print_error_message(&mut store, &runtime_env),
+                "writeBufferMessage" => runtime::write_buffer_message(&mut store, &runtime_env),
+            }
+        };
+        let instance = Instance::new(&mut store, module, &import_object)?;
+        runtime_env.as_mut(&mut store).set_instance(&instance);
+
+        let mut wasm = Self {
+            module: module.clone(),
+            instance,
+            memory: SafeMemory::new(memory, 0, vec![]),
+            runtime_env,
+            version: 0,
+            shared_rw_memory: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            profiler: None,
+        };
+        // Circom 2 circuits export their memory rather than importing ours.
+        if let Some(memory) = wasm.exported_memory() {
+            wasm.memory = SafeMemory::new(memory, 0, vec![]);
+        }
+
+        Ok((wasm, store))
+    }
+
+    /// Returns the memory of the instance.
+    pub fn memory(&self) -> &SafeMemory {
+        &self.memory
+    }
+
+    /// Returns the memory exported by the instance, if any.
+    fn exported_memory(&self) -> Option<Memory> {
+        self.instance.exports.get_memory("memory").ok().cloned()
+    }
+
+    /// Returns the value of the exported `i64` global `name`, which counts events, and resets it to
+    /// zero.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn take_counter(&self, store: &mut impl AsStoreMut, name: &str) -> Result<u64> {
+        let global = self
+            .instance
+            .exports
+            .get_global(name)
+            .map_err(|_| MissingExports(vec![name.to_string()]))?;
+        let Value::I64(count) = global.get(store) else {
+            return Err(UnexpectedReturn(name.to_string()));
+        };
+        global.set(store, Value::I64(0))?;
+        Ok(count as u64)
+    }
+
+    /// Returns the exported function `name`.
+    pub fn func(&self, name: &str) -> Result<&Function> {
+        self.instance
+            .exports
+            .get_function(name)
+            .map_err(|_| MissingExports(vec![name.to_string()]))
+    }
+}
+
+impl WitnessCalculator<Wasm> {
+    /// Constructs a [`WitnessCalculator`] from a file containing a WebAssembly module, instrumented to
+    /// count the calls and the instructions of each function of the module.
+    ///
+    /// The counters are reported with [`WitnessCalculator::calculate_witness_with_profile`]. Instrumented modules run
+    /// slower, so that profiling is only meant to find out which templates of a circuit dominate the
+    /// time spent calculating witnesses.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the WebAssembly module cannot be loaded or instantiated.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn with_profiling(path: impl AsRef<Path>) -> Result<Self> {
+        let profiler = Arc::new(Profiler::default());
+        cfg_if::cfg_if! {
+            if #[cfg(feature = "llvm")] {
+                let mut compiler = LLVM::new();
+            } else {
+                let mut compiler = Cranelift::default();
+            }
+        }
+        compiler.push_middleware(profiler.clone());
+        let store = Store::new(compiler);
+        let module = Wasm::compile_in(&store, path.as_ref())?;
+
+        let (mut instance, store) = Wasm::instantiate_in(&module, store)?;
+        instance.profiler = Some(profiler);
+        Self::from_instance(instance, store)
+    }
+
+    /// Creates a [`Store`] with the compiler selected by the enabled features.
+    pub(crate) fn new_store() -> Store {
+        cfg_if::cfg_if! {
+            if #[cfg(feature = "llvm")] {
+                let compiler = LLVM::new();
+                Store::new(compiler)
+            } else {
+                Store::default()
+            }
+        }
+    }
+
+    /// Constructs a [`WitnessCalculator`] from a WebAssembly module.
+    ///
+    /// After a failed calculation, `module` is instantiated again in a new store sharing the engine
+    /// of `store`.
+    ///
+    /// # Arguments
+    ///
+    /// * `module` - The WebAssembly module representing the circuit.
+    /// * `store` - The WebAssembly store for managing state and execution.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the WebAssembly instance cannot be created, or if it does not export the
+    /// functions needed to calculate witnesses.
+    pub fn from_module(module: Module, store: Store) -> Result<Self> {
+        let (instance, store) = Wasm::instantiate_in(&module, store)?;
+        Self::from_instance(instance, store)
+    }
+
+    /// Sets whether the limbs of field elements are read from and written to the shared read-write
+    /// memory of circom 2 circuits directly in the instance memory, which is the default when the
+    /// circuit exports its memory, or one limb at a time through `readSharedRWMemory` and
+    /// `writeSharedRWMemory`. The setting carries over when the instance is reset.
+    ///
+    /// Either way, circom 2 circuits only copy one signal of the witness at a time to the shared
+    /// memory with `getWitness`, and take inputs one element at a time with `setInputSignal`, since
+    /// they do not export where their signals are.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the start of the shared memory cannot be read from the instance.
+    pub fn set_direct_memory_access(&mut self, direct: bool) -> Result<()> {
+        self.instance
+            .set_direct_memory_access(&mut self.store, direct)
+    }
+
+    /// Calculates the witness for a given set of Circom inputs, along with the calls and
+    /// instructions counted during the calculation, grouped by circom template. The profile is
+    /// `None` if the calculator was not created with [`WitnessCalculator::with_profiling`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the witness calculation fails, see
+    /// [`WitnessCalculator::calculate_witness`], or if the counters cannot be read from the
+    /// instance.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn calculate_witness_with_profile<F: PrimeFieldBits>(
+        &mut self,
+        inputs: Vec<CircomInput<F>>,
+        sanity_check: bool,
+    ) -> Result<(Vec<F>, Option<Profile>)> {
+        // Counters still hold the calls made by earlier calculations or while instantiating the
+        // module.
+        self.take_profile()?;
+        let witness = self.calculate_witness(inputs, sanity_check)?;
+        Ok((witness, self.take_profile()?))
+    }
+
+    /// Takes the calls and instructions counted since the last profile was taken, grouped by circom
+    /// template, or returns `None` if the calculator was not created with
+    /// [`WitnessCalculator::with_profiling`].
+    #[cfg(not(target_arch = "wasm32"))]
+    fn take_profile(&mut self) -> Result<Option<Profile>> {
+        let Some(profiler) = &self.instance.profiler else {
+            return Ok(None);
+        };
+
+        let mut functions = vec![];
+        for (name, calls, instructions) in profiler.counters() {
+            let calls = self.instance.take_counter(&mut self.store, &calls)?;
+            let instructions = self.instance.take_counter(&mut self.store, &instructions)?;
+            functions.push((name, calls, instructions));
+        }
+        Ok(Some(Profile::new(functions)))
+    }
+
+    /// Retrieves the witness buffer as a byte vector.
+    ///
+    /// # Arguments
+    ///
+    /// * `store` - A mutable reference to the WebAssembly store used in computation.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the witness buffer cannot be retrieved, such as a
+    /// [`MemoryAccess`](WitnessCalculatorError::MemoryAccess) error if it is not within the memory.
+    ///
+    /// # Returns
+    ///
+    /// A `Vec<u8>` representing the witness buffer if successful.
+    pub fn get_witness_buffer(&self, store: &mut Store) -> Result<Vec<u8>> {
+        let ptr = u64::from(self.instance.get_ptr_witness_buffer(store)?);
+        let len = u64::from(self.instance.get_n_vars(store)? * self.n64 * 8);
+        let view = self.instance.memory.view(store);
+
+        Ok(view.copy_range_to_vec(ptr..ptr + len)?)
+    }
+}
+
+mod runtime {
+    //! Module `runtime` provides callback hooks for debugging and interacting with the Circom execution environment in
+    //! WebAssembly.
+    //!
+    //! These functions are typically registered as imports into the WebAssembly instance and called by the
+    //! Circom-generated WebAssembly code.
+    use super::{
+        AsStoreMut, Function, FunctionEnv, FunctionEnvMut, Instance, Messages, RuntimeError,
+        SafeMemory, Value, WitnessCalculatorError,
+    };
+    use crate::util::limbs_as_decimal;
+    use log::error;
+
+    /// State shared by the hooks that need to call back into the instance.
+    #[derive(Debug, Default)]
+    pub struct RuntimeEnv {
+        get_message_char: Option<Function>,
+        get_field_num_len32: Option<Function>,
+        read_shared_rw_memory: Option<Function>,
+        /// Memory of the instance, used to read the messages of circom 1 errors.
+        pub memory: Option<SafeMemory>,
+        /// Messages printed by the circuit.
+        pub messages: Messages,
+    }
+
+    impl RuntimeEnv {
+        /// Retrieves the exports the hooks call back into, once the instance is created.
+        pub fn set_instance(&mut self, instance: &Instance) {
+            let get_function = |name| instance.exports.get_function(name).ok().cloned();
+            self.get_message_char = get_function("getMessageChar");
+            self.get_field_num_len32 = get_function("getFieldNumLen32");
+            self.read_shared_rw_memory = get_function("readSharedRWMemory");
+        }
+    }
+
+    /// Creates a function to handle runtime errors occurring within the WebAssembly instance.
+    ///
+    /// This function is invoked when the Circom 1 generated code encounters a runtime error.
+    /// It logs the error details and terminates the execution with the matching
+    /// [`WitnessCalculatorError`].
+    ///
+    /// # Arguments
+    ///
+    /// * `store` - A mutable reference to the WebAssembly store.
+    /// * `env` - The environment shared by the runtime hooks.
+    ///
+    /// # Returns
+    ///
+    /// A [`Function`] that can be called from within the WebAssembly instance.
+    pub fn error(store: &mut impl AsStoreMut, env: &FunctionEnv<RuntimeEnv>) -> Function {
+        #[allow(clippy::many_single_char_names)]
+        fn func(
+            env: FunctionEnvMut<'_, RuntimeEnv>,
+            code: i32,
+            pstr: i32,
+            a: i32,
+            b: i32,
+            c: i32,
+            d: i32,
+        ) -> Result<(), RuntimeError> {
+            // Mirrors the generic case of the JS runtime:
+            // https://github.com/iden3/circom_runtime/blob/master/js/witness_calculator.js#L52-L64
+            let message = env
+                .data()
+                .memory
+                .as_ref()
+                .map(|memory| memory.read_string(&env, pstr as u32 as usize))
+                .unwrap_or_default();
+            let message = format!("{message} {a} {b} {c} {d}");
+            error!("runtime error, exiting early: {code} {message}");
+            Err(RuntimeError::user(Box::new(
+                WitnessCalculatorError::from_circom1_error(code, message),
+            )))
+        }
+        Function::new_typed_with_env(store, env, func)
+    }
+
+    // Function definitions for Circom 2.0
+
+    /// Handles exceptions thrown within the WebAssembly instance for Circom 2.0.
+    ///
+    /// The exception code is mapped to its [`WitnessCalculatorError`], along with the error messages
+    /// printed beforehand, which terminates the execution.
+    pub fn exception_handler(
+        store: &mut impl AsStoreMut,
+        env: &FunctionEnv<RuntimeEnv>,
+    ) -> Function {
+        fn func(mut env: FunctionEnvMut<'_, RuntimeEnv>, code: i32) -> Result<(), RuntimeError> {
+            let error = env.data_mut().messages.exception(code);
+            Err(RuntimeError::user(Box::new(error)))
+        }
+        Function::new_typed_with_env(store, env, func)
+    }
+
+    /// Debugging function to display the shared read-write memory in Circom 2.0.
+    ///
+    /// The field element held in the shared memory is appended, in decimal, to the message being
+    /// buffered for the [`LogSink`](crate::witness::LogSink).
+    pub fn show_memory(store: &mut impl AsStoreMut, env: &FunctionEnv<RuntimeEnv>) -> Function {
+        fn func(mut env: FunctionEnvMut<'_, RuntimeEnv>) -> Result<(), RuntimeError> {
+            let (Some(get_field_num_len32), Some(read_shared_rw_memory)) = (
+                env.data().get_field_num_len32.clone(),
+                env.data().read_shared_rw_memory.clone(),
+            ) else {
+                return Ok(());
+            };
+
+            let [Value::I32(n32)] = *get_field_num_len32.call(&mut env, &[])? else {
+                return Ok(());
+            };
+            let mut limbs = Vec::with_capacity(n32 as usize);
+            for j in 0..n32 {
+                let [Value::I32(limb)] = *read_shared_rw_memory.call(&mut env, &[j.into()])? else {
+                    return Ok(());
+                };
+                limbs.push(limb as u32);
+            }
+
+            env.data_mut()
+                .messages
+                .buffer_message(&limbs_as_decimal(&limbs));
+            Ok(())
+        }
+        Function::new_typed_with_env(store, env, func)
+    }
+
+    /// Logs error messages for Circom 2.0.
+    ///
+    /// The message is sent to the [`LogSink`](crate::witness::LogSink) at the error level, and kept to be reported with the
+    /// exception that follows.
+    pub fn print_error_message(
+        store: &mut impl AsStoreMut,
+        env: &FunctionEnv<RuntimeEnv>,
+    ) -> Function {
+        fn func(mut env: FunctionEnvMut<'_, RuntimeEnv>) -> Result<(), RuntimeError> {
+            let message = read_message(&mut env)?;
+            env.data_mut().messages.print_error_message(message);
+            Ok(())
+        }
+        Function::new_typed_with_env(store, env, func)
+    }
+
+    /// Writes buffer messages for Circom 2.0.
+    ///
+    /// Messages are buffered, separated by spaces, until a single newline is written. The buffered
+    /// line is then sent to the [`LogSink`](crate::witness::LogSink).
+    pub fn write_buffer_message(
+        store: &mut impl AsStoreMut,
+        env: &FunctionEnv<RuntimeEnv>,
+    ) -> Function {
+        fn func(mut env: FunctionEnvMut<'_, RuntimeEnv>) -> Result<(), RuntimeError> {
+            let message = read_message(&mut env)?;
+            env.data_mut().messages.write_buffer_message(message);
+            Ok(())
+        }
+        Function::new_typed_with_env(store, env, func)
+    }
+
+    /// Reads the message the circuit wrote to its message buffer, one character at a time.
+    fn read_message(env: &mut FunctionEnvMut<'_, RuntimeEnv>) -> Result<String, RuntimeError> {
+        let mut message = String::new();
+        let Some(get_message_char) = env.data().get_message_char.clone() else {
+            return Ok(message);
+        };
+
+        loop {
+            let [Value::I32(c)] = *get_message_char.call(env, &[])? else {
+                return Ok(message);
+            };
+            if c == 0 {
+                return Ok(message);
+            }
+            message.push(char::from(c as u8));
+        }
+    }
+
+    // Common utility functions for Circom 1 and Circom 2.0
+
+    /// Logs signals during Circom computation.
+    ///
+    /// This function is a stub and currently does nothing.
+    pub fn log_signal(store: &mut impl AsStoreMut) -> Function {
+        #[allow(unused)]
+        fn func(a: i32, b: i32) {}
+        Function::new_typed(store, func)
+    }
+
+    /// Logs component-related messages during Circom computation.
+    ///
+    /// This function is a stub and currently does nothing.
+    pub fn log_component(store: &mut impl AsStoreMut) -> Function {
+        #[allow(unused)]
+        fn func(a: i32) {}
+        Function::new_typed(store, func)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ff::Field;
+    use pasta_curves::vesta;
+
+    use super::*;
+    use crate::witness::witness_calculator::tests::{Bn254, Goldilocks};
+
+    fn sha256_input<F: ff::PrimeField>() -> Vec<CircomInput<F>> {
+        vec![CircomInput::new("arg_in".into(), vec![F::ZERO, F::ZERO])]
+    }
+
+    #[test]
+    fn test_shared_rw_memory() {
+        let mut wtns = WitnessCalculator::new("circom/sha256/circom_sha256.wasm").unwrap();
+        let witness = wtns
+            .calculate_witness::<vesta::Base>(sha256_input(), true)
+            .unwrap();
+
+        // Going through `readSharedRWMemory` and `writeSharedRWMemory` yields the same witness as
+        // accessing the shared memory directly.
+        assert!(wtns.instance.shared_rw_memory.is_some());
+        wtns.set_direct_memory_access(false).unwrap();
+        assert!(wtns.instance.shared_rw_memory.is_none());
+        let other_witness = wtns
+            .calculate_witness::<vesta::Base>(sha256_input(), true)
+            .unwrap();
+        assert_eq!(witness, other_witness);
+
+        wtns.set_direct_memory_access(true).unwrap();
+        assert!(wtns.instance.shared_rw_memory.is_some());
+    }
+
+    #[test]
+    fn test_calculate_witness_circom1() {
+        // A build of the circom 1 compiler, see circom/multiplier_v1/README.md.
+        let mut wtns = WitnessCalculator::new("circom/multiplier_v1/multiplier.wasm").unwrap();
+        let free_pos = wtns.instance.memory.free_pos(&wtns.store).unwrap();
+        assert_eq!(wtns.circom_version, 1);
+        assert!(wtns.check_prime::<Bn254>().is_ok());
+        assert!(wtns.check_prime::<vesta::Base>().is_err());
+
+        let inputs = |a: Bn254, b: Bn254| {
+            vec![
+                CircomInput::new("a".into(), vec![a]),
+                CircomInput::new("b".into(), vec![b]),
+            ]
+        };
+        let (a, b) = (Bn254::from(3), Bn254::from(11));
+        let witness = wtns.calculate_witness(inputs(a, b), true).unwrap();
+        assert_eq!(witness, vec![Bn254::ONE, Bn254::from(33), a, b]);
+
+        // Negative and long inputs are written in their long form.
+        for a in [-Bn254::from(12345678), Bn254::from(2).invert().unwrap()] {
+            let b = Bn254::from(11);
+            let witness = wtns.calculate_witness(inputs(a, b), true).unwrap();
+            assert_eq!(witness, vec![Bn254::ONE, a * b, a, b]);
+        }
+
+        // Unknown inputs are reported by the circuit.
+        let wrong_inputs = vec![CircomInput::new("d".into(), vec![a])];
+        assert!(matches!(
+            wtns.calculate_witness(wrong_inputs, true),
+            Err(WitnessCalculatorError::CircuitError { code: 3, .. })
+        ));
+
+        // The instance is restored to its state right after instantiation after the failure.
+        assert_eq!(
+            wtns.instance.memory.free_pos(&wtns.store).unwrap(),
+            free_pos
+        );
+        assert_eq!(
+            wtns.calculate_witness(inputs(a, b), true).unwrap()[1],
+            Bn254::from(33)
+        );
+    }
+
+    #[test]
+    fn test_load_errors() {
+        let err = WitnessCalculator::new("circom/mock/missing.wasm").unwrap_err();
+        assert!(matches!(err, WitnessCalculatorError::ModuleLoad(_)));
+
+        let store = WitnessCalculator::new_store();
+        let module = Module::new(
+            &store,
+            "(module (import \"env\" \"table\" (table 1 funcref)))",
+        )
+        .unwrap();
+        let err = WitnessCalculator::from_module(module, store).unwrap_err();
+        assert!(matches!(err, WitnessCalculatorError::Instantiation(_)));
+    }
+
+    #[test]
+    fn test_missing_exports() {
+        let store = WitnessCalculator::new_store();
+        let module = Module::new(&store, "(module)").unwrap();
+        let err = WitnessCalculator::from_module(module, store).unwrap_err();
+        assert!(matches!(err, MissingExports(missing) if missing.len() == 7));
+
+        let store = WitnessCalculator::new_store();
+        let wat = r#"(module
+            (func (export "getVersion") (result i32) (i32.const 2))
+            (func (export "init") (param i32))
+            (func (export "getFieldNumLen32") (result i32) (i32.const 8)))"#;
+        let module = Module::new(&store, wat).unwrap();
+        let err = WitnessCalculator::from_module(module, store).unwrap_err();
+        assert!(matches!(
+            err,
+            MissingExports(missing) if missing == [
+                "getRawPrime",
+                "readSharedRWMemory",
+                "writeSharedRWMemory",
+                "setInputSignal",
+                "getWitnessSize",
+                "getWitness",
+            ]
+        ));
+    }
+
+    #[test]
+    fn test_reset() {
+        let mut wtns = WitnessCalculator::new("circom/mock/adder_goldilocks.wat").unwrap();
+        let memory = |wtns: &WitnessCalculator| {
+            wtns.instance
+                .memory
+                .view(&wtns.store)
+                .copy_to_vec()
+                .unwrap()
+        };
+        let initial_memory = memory(&wtns);
+        let inputs = |a: u64, b: u64| {
+            vec![
+                CircomInput::new("a".into(), vec![Goldilocks::from(a)]),
+                CircomInput::new("b".into(), vec![Goldilocks::from(b)]),
+            ]
+        };
+
+        // The instance is instantiated again after a failed calculation.
+        wtns.calculate_witness(inputs(1, 1), true).unwrap_err();
+        assert_eq!(memory(&wtns), initial_memory);
+        assert_eq!(
+            wtns.calculate_witness(inputs(1, 2), true).unwrap()[1],
+            Goldilocks::from(3)
+        );
+
+        // And on request after a successful one.
+        assert_ne!(memory(&wtns), initial_memory);
+        wtns.reset().unwrap();
+        assert_eq!(memory(&wtns), initial_memory);
+    }
+
+    #[test]
+    fn test_reset_circom2() {
+        let path = "circom/sha256/circom_sha256.wasm";
+        let mut wtns = WitnessCalculator::new(path).unwrap();
+        let memory = |wtns: &WitnessCalculator| {
+            wtns.instance
+                .memory
+                .view(&wtns.store)
+                .copy_to_vec()
+                .unwrap()
+        };
+        let initial_memory = memory(&wtns);
+
+        let witness = wtns
+            .calculate_witness::<vesta::Base>(sha256_input(), true)
+            .unwrap();
+        assert_ne!(memory(&wtns), initial_memory);
+        wtns.reset().unwrap();
+        assert_eq!(memory(&wtns), initial_memory);
+        let other_witness = wtns
+            .calculate_witness::<vesta::Base>(sha256_input(), true)
+            .unwrap();
+        assert_eq!(witness, other_witness);
+
+        // Access to the shared memory through exports carries over.
+        wtns.set_direct_memory_access(false).unwrap();
+        wtns.reset().unwrap();
+        assert!(wtns.instance.shared_rw_memory.is_none());
+        assert_eq!(
+            wtns.calculate_witness::<vesta::Base>(sha256_input(), true)
+                .unwrap(),
+            witness
+        );
+    }
+
+    #[test]
+    fn test_profiling() {
+        let path = "circom/sha256/circom_sha256.wasm";
+        let mut wtns = WitnessCalculator::new(path).unwrap();
+        let (expected, profile) = wtns
+            .calculate_witness_with_profile::<vesta::Base>(sha256_input(), true)
+            .unwrap();
+        assert_eq!(profile, None);
+
+        let mut wtns = WitnessCalculator::with_profiling(path).unwrap();
+        let (witness, profile) = wtns
+            .calculate_witness_with_profile::<vesta::Base>(sha256_input(), true)
+            .unwrap();
+        assert_eq!(witness, expected);
+
+        let rotr = |profile: Profile| {
+            profile
+                .templates
+                .into_iter()
+                .find(|template| template.name == "RotR")
+                .unwrap()
+        };
+        let profile = profile.unwrap();
+        assert!(!profile
+            .templates
+            .iter()
+            .any(|template| template.name == "getVersion"));
+        assert!(profile
+            .templates
+            .windows(2)
+            .all(|w| w[0].instructions >= w[1].instructions));
+
+        // Each profile only counts its own calculation.
+        wtns.calculate_witness::<vesta::Base>(sha256_input(), true)
+            .unwrap();
+        let (_, other_profile) = wtns
+            .calculate_witness_with_profile::<vesta::Base>(sha256_input(), true)
+            .unwrap();
+        assert_eq!(rotr(other_profile.unwrap()), rotr(profile.clone()));
+
+        // The profiler carries over to the instance created again by a reset.
+        wtns.reset().unwrap();
+        let (_, other_profile) = wtns
+            .calculate_witness_with_profile::<vesta::Base>(sha256_input(), true)
+            .unwrap();
+        assert_eq!(other_profile.unwrap(), profile);
+
+        let rotr = rotr(profile);
+        assert_eq!(rotr.calls, 576);
+        assert!(rotr.instructions > 0);
+    }
+}
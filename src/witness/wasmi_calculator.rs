@@ -0,0 +1,336 @@
+// Copyright (c) Lurk Lab
+// SPDX-License-Identifier: MIT
+//! # Wasmi witness calculator module
+//!
+//! The `wasmi_calculator` module provides [`WasmiInstance`], which runs circuits compiled with circom 2 by interpreting
+//! their WebAssembly with [wasmi](https://github.com/wasmi-labs/wasmi), rather than compiling it to native code with
+//! wasmer. It targets environments where code cannot be generated at runtime, and is available with the `wasmi`
+//! feature.
+//!
+//! Both engines implement the [`Circom`] trait, so that a [`WasmiWitnessCalculator`] is a [`WitnessCalculator`]
+//! calling the same functions exported by the circuit.
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+use wasmi::{Caller, Engine, Extern, Func, Linker, Module, Store, Val};
+
+use super::{
+    witness_calculator::Messages,
+    Circom, Result, WitnessCalculator, WitnessCalculatorError,
+    WitnessCalculatorError::{MissingExports, UnexpectedReturn, VersionNotSupported},
+};
+use crate::util::limbs_as_decimal;
+
+/// A [`WitnessCalculator`] interpreting the WebAssembly of circuits with wasmi.
+///
+/// Only circuits compiled with circom 2 are supported.
+pub type WasmiWitnessCalculator = WitnessCalculator<WasmiInstance>;
+
+/// Represents a WebAssembly instance for Circom computations, run by wasmi.
+#[derive(Clone, Debug)]
+pub struct WasmiInstance {
+    /// The module the instance was created from, instantiated again to reset the instance.
+    module: Arc<Module>,
+    /// Functions exported by the instance, which wasmi only looks up through the store.
+    funcs: HashMap<String, Func>,
+}
+
+impl Circom for WasmiInstance {
+    type Store = Store<Messages>;
+    /// Wasmi modules cannot be cloned, and are shared instead.
+    type Module = Arc<Module>;
+
+    /// Modules are read in the binary or the text format, as wasmer does.
+    fn compile(path: &Path) -> Result<Arc<Module>> {
+        let wasm = std::fs::read(path)?;
+        let wasm = wat::parse_bytes(&wasm)?;
+        Ok(Arc::new(Module::new(&Engine::default(), &wasm)?))
+    }
+
+    /// Circom 1 circuits, which import their memory rather than exporting it, cannot be instantiated.
+    fn instantiate(module: &Arc<Module>) -> Result<(Self, Store<Messages>)> {
+        if module
+            .imports()
+            .any(|import| import.module() == "env" && import.name() == "memory")
+        {
+            return Err(VersionNotSupported(1));
+        }
+
+        let mut store = Store::new(module.engine(), Messages::default());
+        let instance = instantiate(&mut store, module)?;
+        Ok((instance, store))
+    }
+
+    fn has_func(&self, name: &str) -> bool {
+        self.funcs.contains_key(name)
+    }
+
+    fn call(&self, store: &mut Store<Messages>, name: &str, args: &[u32]) -> Result<Option<u32>> {
+        let func = self
+            .funcs
+            .get(name)
+            .ok_or_else(|| MissingExports(vec![name.to_string()]))?;
+        let params = args
+            .iter()
+            .map(|&arg| Val::I32(arg as i32))
+            .collect::<Vec<_>>();
+        let mut results = [Val::I32(0)];
+        let results = match func.ty(&*store).results().len() {
+            0 => &mut results[..0],
+            1 => &mut results[..],
+            _ => return Err(UnexpectedReturn(name.to_string())),
+        };
+        func.call(&mut *store, &params, results)?;
+
+        match *results {
+            [] => Ok(None),
+            [Val::I32(value)] => Ok(Some(value as u32)),
+            _ => Err(UnexpectedReturn(name.to_string())),
+        }
+    }
+
+    fn messages<'a>(&self, store: &'a mut Store<Messages>) -> &'a mut Messages {
+        store.data_mut()
+    }
+
+    /// Instantiates the module again in a new store, which keeps the messages of the current one.
+    fn reset(&mut self, store: &mut Store<Messages>) -> Result<()> {
+        let messages = std::mem::take(store.data_mut());
+        let mut new_store = Store::new(self.module.engine(), messages);
+        *self = instantiate(&mut new_store, &self.module)?;
+        *store = new_store;
+
+        Ok(())
+    }
+}
+
+impl WitnessCalculator<WasmiInstance> {
+    /// Constructs a [`WasmiWitnessCalculator`] from the bytes of a WebAssembly module.
+    ///
+    /// # Arguments
+    ///
+    /// * `wasm` - The WebAssembly module representing the circuit, in binary format.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the WebAssembly module cannot be compiled or instantiated.
+    pub fn from_bytes(wasm: &[u8]) -> Result<Self> {
+        let module = Module::new(&Engine::default(), wasm)?;
+        Self::instantiate(&Arc::new(module))
+    }
+}
+
+/// Instantiates the module, with the runtime hooks circom 2 circuits import.
+fn instantiate(store: &mut Store<Messages>, module: &Arc<Module>) -> Result<WasmiInstance> {
+    let mut linker = Linker::<Messages>::new(module.engine());
+    linker
+        .func_wrap("runtime", "exceptionHandler", runtime::exception_handler)
+        .and_then(|linker| linker.func_wrap("runtime", "showSharedRWMemory", runtime::show_memory))
+        .and_then(|linker| {
+            linker.func_wrap("runtime", "printErrorMessage", runtime::print_error_message)
+        })
+        .and_then(|linker| {
+            linker.func_wrap(
+                "runtime",
+                "writeBufferMessage",
+                runtime::write_buffer_message,
+            )
+        })
+        .map_err(wasmi::Error::from)?;
+
+    let instance = linker
+        .instantiate(&mut *store, module)?
+        .start(&mut *store)?;
+    let funcs = instance
+        .exports(&*store)
+        .filter_map(|export| {
+            let name = export.name().to_string();
+            export.into_func().map(|func| (name, func))
+        })
+        .collect();
+    Ok(WasmiInstance {
+        module: module.clone(),
+        funcs,
+    })
+}
+
+mod runtime {
+    //! Module `runtime` provides the callback hooks imported by circom 2 circuits, for the wasmi backend.
+    //!
+    //! They mirror the hooks of the wasmer backend, and share their handling of messages and exceptions.
+    use super::{limbs_as_decimal, Caller, Extern, Messages, Val, WitnessCalculatorError};
+
+    /// Handles exceptions thrown within the WebAssembly instance, which terminates the execution with the matching
+    /// [`WitnessCalculatorError`].
+    pub fn exception_handler(
+        mut caller: Caller<'_, Messages>,
+        code: i32,
+    ) -> Result<(), wasmi::Error> {
+        let error: WitnessCalculatorError = caller.data_mut().exception(code);
+        Err(wasmi::Error::host(error))
+    }
+
+    /// Appends the field element held in the shared read-write memory, in decimal, to the message being buffered.
+    pub fn show_memory(mut caller: Caller<'_, Messages>) -> Result<(), wasmi::Error> {
+        let (Some(n32), Some(read_shared_rw_memory)) = (
+            call_i32(&mut caller, "getFieldNumLen32", &[])?,
+            caller
+                .get_export("readSharedRWMemory")
+                .and_then(Extern::into_func),
+        ) else {
+            return Ok(());
+        };
+
+        let mut limbs = Vec::with_capacity(n32 as usize);
+        for j in 0..n32 {
+            let mut limb = [Val::I32(0)];
+            read_shared_rw_memory.call(&mut caller, &[Val::I32(j)], &mut limb)?;
+            let [Val::I32(limb)] = limb else {
+                return Ok(());
+            };
+            limbs.push(limb as u32);
+        }
+
+        caller.data_mut().buffer_message(&limbs_as_decimal(&limbs));
+        Ok(())
+    }
+
+    /// Logs error messages, which are kept to be reported with the exception that follows.
+    pub fn print_error_message(mut caller: Caller<'_, Messages>) -> Result<(), wasmi::Error> {
+        let message = read_message(&mut caller)?;
+        caller.data_mut().print_error_message(message);
+        Ok(())
+    }
+
+    /// Writes buffer messages, which are sent to the [`LogSink`](super::LogSink) line by line.
+    pub fn write_buffer_message(mut caller: Caller<'_, Messages>) -> Result<(), wasmi::Error> {
+        let message = read_message(&mut caller)?;
+        caller.data_mut().write_buffer_message(message);
+        Ok(())
+    }
+
+    /// Reads the message the circuit wrote to its message buffer, one character at a time.
+    fn read_message(caller: &mut Caller<'_, Messages>) -> Result<String, wasmi::Error> {
+        let mut message = String::new();
+        while let Some(c) = call_i32(caller, "getMessageChar", &[])? {
+            if c == 0 {
+                break;
+            }
+            message.push(char::from(c as u8));
+        }
+        Ok(message)
+    }
+
+    /// Calls the exported function `name`, if the instance exports it and it returns a single `i32`.
+    fn call_i32(
+        caller: &mut Caller<'_, Messages>,
+        name: &str,
+        params: &[Val],
+    ) -> Result<Option<i32>, wasmi::Error> {
+        let Some(func) = caller.get_export(name).and_then(Extern::into_func) else {
+            return Ok(None);
+        };
+        if func.ty(&*caller).results().len() != 1 {
+            return Ok(None);
+        }
+
+        let mut result = [Val::I32(0)];
+        func.call(&mut *caller, params, &mut result)?;
+        match result {
+            [Val::I32(value)] => Ok(Some(value)),
+            _ => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ff::Field;
+    use pasta_curves::vesta;
+
+    use super::*;
+    use crate::r1cs::CircomInput;
+    use crate::witness::WitnessCalculatorPool;
+
+    /// Checks that both engines calculate the same witness.
+    #[cfg(feature = "wasmer")]
+    fn assert_same_witness(path: &str, inputs: Vec<CircomInput<vesta::Base>>) {
+        let mut wasmi = WasmiWitnessCalculator::load(path).unwrap();
+        let mut wasmer = WitnessCalculator::<crate::witness::Wasm>::load(path).unwrap();
+
+        let witness = wasmi.calculate_witness(inputs.clone(), true).unwrap();
+        assert_eq!(witness, wasmer.calculate_witness(inputs, true).unwrap());
+    }
+
+    #[cfg(feature = "wasmer")]
+    #[test]
+    fn test_sha256() {
+        let inputs = vec![CircomInput::new(
+            "arg_in".into(),
+            vec![vesta::Base::ZERO, vesta::Base::ONE],
+        )];
+        assert_same_witness("circom/sha256/circom_sha256.wasm", inputs);
+    }
+
+    #[cfg(feature = "wasmer")]
+    #[test]
+    fn test_keccak() {
+        let inputs = vec![CircomInput::new(
+            "in".into(),
+            (0..256).map(|i| vesta::Base::from(i % 3 == 0)).collect(),
+        )];
+        assert_same_witness("circom/keccak/circom_keccak256.wasm", inputs);
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut wtns = WasmiWitnessCalculator::load("circom/sha256/circom_sha256.wasm").unwrap();
+        let input = CircomInput::new("arg_in".into(), vec![vesta::Base::ZERO; 2]);
+
+        let err = wtns
+            .calculate_witness::<vesta::Base>(vec![CircomInput::new("foo".into(), vec![])], true)
+            .unwrap_err();
+        assert!(matches!(err, WitnessCalculatorError::UnknownInput { .. }));
+
+        // Exceptions raised by the circuit are returned as their error, and the instance recovers.
+        let err = wtns
+            .calculate_witness(vec![input.clone(), input.clone()], true)
+            .unwrap_err();
+        assert!(matches!(err, WitnessCalculatorError::TooManySignalValues));
+        let witness = wtns.calculate_witness(vec![input], true).unwrap();
+        assert_eq!(witness.len(), 29823);
+
+        let wat = r#"(module (func (export "getVersion") (result i32) (i32.const 2)))"#;
+        let err = WasmiWitnessCalculator::from_bytes(&wat::parse_str(wat).unwrap()).unwrap_err();
+        assert!(
+            matches!(err, WitnessCalculatorError::MissingExports(missing) if missing.len() == 8)
+        );
+
+        let err = WasmiWitnessCalculator::load("circom/mock/missing.wasm").unwrap_err();
+        assert!(matches!(err, WitnessCalculatorError::Io(_)));
+    }
+
+    #[test]
+    fn test_pool() {
+        let pool =
+            WitnessCalculatorPool::<WasmiInstance>::load("circom/sha256/circom_sha256.wasm", 2)
+                .unwrap();
+        let inputs = vec![CircomInput::new(
+            "arg_in".into(),
+            vec![vesta::Base::ZERO; 2],
+        )];
+
+        let witnesses = std::thread::scope(|s| {
+            let handles = (0..3)
+                .map(|_| s.spawn(|| pool.get().unwrap().calculate_witness(inputs.clone(), true)))
+                .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap().unwrap())
+                .collect::<Vec<_>>()
+        });
+        assert!(witnesses.windows(2).all(|w| w[0] == w[1]));
+        assert!(pool.instantiated() <= 2);
+    }
+}
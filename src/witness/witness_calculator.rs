@@ -12,47 +12,134 @@
 //! which is responsible for initializing the WebAssembly instance, allocating memory, and
 //! performing computations to generate the witness.
 //!
-//! The [`WitnessCalculator`] struct interacts with the WebAssembly instance using the
-//! WebAssembly [`Store`], and manages memory through a [`SafeMemory`] object. It supports both
-//! Circom version 1 and version 2, providing the necessary interface to handle differences
-//! in their execution environments.
+//! The [`WitnessCalculator`] struct interacts with the WebAssembly instance through the [`Circom`]
+//! trait, so that the same calculator runs on every engine implementing it: wasmer with the
+//! `wasmer` feature, enabled by default, and wasmi with the `wasmi` feature. It supports both Circom version 1 and version 2, providing the necessary
+//! interface to handle differences in their execution environments.
 //!
-//! Additionally, this module contains utility functions for converting between field elements
-//! and their byte representations, as well as the `runtime` submodule, which provides callback
-//! hooks for debugging and error handling within the WebAssembly environment.
-use anyhow::Result;
-use ff::PrimeFieldBits;
-use ruint::aliases::U256;
-use wasmer::{
-    imports, AsStoreMut, Function, Instance, Memory, MemoryType, Module, RuntimeError, Store,
+//! Additionally, this module defines [`Messages`], which holds the messages printed by a circuit
+//! independently of the engine running it.
+use ff::{PrimeField, PrimeFieldBits};
+use log::{log, Level};
+use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
+
+use super::{
+    check_prime, fnv, Circom, DefaultEngine, Result, WitnessCalculatorError,
+    WitnessCalculatorError::{MissingExports, UnsupportedFieldSize, VersionNotSupported},
 };
-#[cfg(feature = "llvm")]
-use wasmer_compiler_llvm::LLVM;
-
-use super::{fnv, Circom, SafeMemory, Wasm};
-use crate::error::ReaderError::WitnessVersionNotSupported;
-use crate::r1cs::CircomInput;
-use crate::util::{ff_as_limbs, limbs_as_ff, limbs_as_u256};
+use crate::r1cs::{CircomInput, SymbolTable};
+use crate::util::{ff_as_limbs, limbs_as_ff};
 
 /// A struct for managing and calculating witnesses in Circom circuits.
-/// It utilizes a WebAssembly instance to run computations and manage state.
+/// It utilizes a WebAssembly instance, run by the engine `C`, to run computations and manage state.
 #[derive(Debug)]
-pub struct WitnessCalculator {
-    pub instance: Wasm,
-    pub store: Store,
-    pub memory: SafeMemory,
+pub struct WitnessCalculator<C: Circom = DefaultEngine> {
+    pub instance: C,
+    pub store: C::Store,
     pub n64: u32,
     pub circom_version: u32,
+    /// The size of the field elements, in 32-bit units.
+    n32: u32,
+    /// The little endian [`u32`] limbs of the prime the circuit was compiled for.
+    prime: Vec<u32>,
+    symbols: Option<SymbolTable>,
+    /// The hash of the WebAssembly module the calculator was loaded from, checked by
+    /// [`super::WitnessCache`].
+    #[cfg(feature = "cache")]
+    pub(super) module_hash: Option<[u8; 32]>,
+}
+
+/// Destination of the messages printed by a circuit through circom's `log()`, and of the error
+/// messages it prints before failing.
+#[derive(Clone, Default)]
+pub enum LogSink {
+    /// Forwards messages to the [`log`] crate, error messages at the error level and all others at
+    /// the info level.
+    #[default]
+    Log,
+    /// Calls the given function with each message.
+    Callback(Arc<dyn Fn(&str) + Send + Sync>),
+    /// Collects messages, to be retrieved with [`WitnessCalculator::take_logs`].
+    Collect,
+}
+
+impl fmt::Debug for LogSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Log => write!(f, "Log"),
+            Self::Callback(_) => write!(f, "Callback"),
+            Self::Collect => write!(f, "Collect"),
+        }
+    }
 }
 
-// Error type to signal end of execution.
-// From https://docs.wasmer.io/integrations/examples/exit-early
-#[derive(thiserror::Error, Debug, Clone, Copy)]
-#[error("{0}")]
-struct ExitCode(u32);
+/// Messages printed by a circuit, independently of the engine running it.
+#[derive(Debug, Default)]
+pub struct Messages {
+    /// Destination of the messages printed by the circuit.
+    pub sink: LogSink,
+    /// Messages collected by a [`LogSink::Collect`] sink.
+    pub logs: Vec<String>,
+    /// Line being buffered by `writeBufferMessage` and `showSharedRWMemory`.
+    buffer: String,
+    /// Error messages printed through `printErrorMessage` since the calculation started.
+    errors: Vec<String>,
+}
+
+impl Messages {
+    /// Clears the messages buffered by a previous calculation.
+    pub fn reset(&mut self) {
+        self.buffer.clear();
+        self.errors.clear();
+    }
+
+    /// Appends a message to the line being buffered.
+    pub fn buffer_message(&mut self, message: &str) {
+        if !self.buffer.is_empty() {
+            self.buffer.push(' ');
+        }
+        self.buffer.push_str(message);
+    }
+
+    /// Handles a message written through `writeBufferMessage`: a single newline sends the
+    /// buffered line to the [`LogSink`], other messages are buffered.
+    pub fn write_buffer_message(&mut self, message: String) {
+        if message == "\n" {
+            let line = std::mem::take(&mut self.buffer);
+            self.emit(line, Level::Info);
+        } else {
+            self.buffer_message(&message);
+        }
+    }
+
+    /// Handles a message printed through `printErrorMessage`, which is sent to the [`LogSink`]
+    /// at the error level and kept to be reported with the exception that follows.
+    pub fn print_error_message(&mut self, message: String) {
+        self.errors.push(message.clone());
+        self.emit(message, Level::Error);
+    }
+
+    /// Maps a circom 2 exception code to its [`WitnessCalculatorError`], along with the error
+    /// messages printed beforehand.
+    pub fn exception(&mut self, code: i32) -> WitnessCalculatorError {
+        let errors = std::mem::take(&mut self.errors);
+        WitnessCalculatorError::from_exception_code(code, errors)
+    }
+
+    fn emit(&mut self, message: String, level: Level) {
+        match &self.sink {
+            LogSink::Log => log!(level, "{message}"),
+            LogSink::Callback(callback) => callback(&message),
+            LogSink::Collect => self.logs.push(message),
+        }
+    }
+}
 
 impl WitnessCalculator {
-    /// Constructs a new [`WitnessCalculator`] from a given file path.
+    /// Constructs a new [`WitnessCalculator`] from a given file path, running on the
+    /// [`DefaultEngine`].
     ///
     /// # Arguments
     ///
@@ -61,11 +148,12 @@ impl WitnessCalculator {
     /// # Errors
     ///
     /// Returns an error if the WebAssembly module cannot be loaded or instantiated.
-    pub fn new(path: impl AsRef<std::path::Path>) -> Result<Self> {
+    pub fn new(path: impl AsRef<Path>) -> Result<Self> {
         Self::from_file(path)
     }
 
-    /// Constructs a [`WitnessCalculator`] from a file containing a WebAssembly module.
+    /// Constructs a [`WitnessCalculator`] from a file containing a WebAssembly module, running on
+    /// the [`DefaultEngine`].
     ///
     /// # Arguments
     ///
@@ -74,82 +162,142 @@ impl WitnessCalculator {
     /// # Errors
     ///
     /// Returns an error if the WebAssembly module cannot be loaded or instantiated.
-    pub fn from_file(path: impl AsRef<std::path::Path>) -> Result<Self> {
-        cfg_if::cfg_if! {
-            if #[cfg(feature = "llvm")] {
-                let compiler = LLVM::new();
-                let store = Store::new(compiler);
-            } else {
-                let store = Store::default();
-            }
-        }
-        let module = Module::from_file(&store, path)?;
-        Self::from_module(module, store)
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        Self::load(path)
     }
+}
 
-    /// Constructs a [`WitnessCalculator`] from a WebAssembly module.
+impl<C: Circom> WitnessCalculator<C> {
+    /// Constructs a [`WitnessCalculator`] running on the engine `C` from a file containing a
+    /// WebAssembly module.
     ///
     /// # Arguments
     ///
-    /// * `module` - The WebAssembly module representing the circuit.
-    /// * `store` - The WebAssembly store for managing state and execution.
+    /// * `path` - The file path to the WebAssembly module representing the circuit.
     ///
     /// # Errors
     ///
-    /// Returns an error if the WebAssembly instance cannot be created.
-    pub fn from_module(module: Module, mut store: Store) -> Result<Self> {
-        // Set up the memory
-        let memory = Memory::new(&mut store, MemoryType::new(2000, None, false))?;
-        let import_object = imports! {
-            "env" => {
-                "memory" => memory.clone(),
-            },
-            // Host function callbacks from the WASM
-            "runtime" => {
-                "error" => runtime::error(&mut store),
-                "logSetSignal" => runtime::log_signal(&mut store),
-                "logGetSignal" => runtime::log_signal(&mut store),
-                "logFinishComponent" => runtime::log_component(&mut store),
-                "logStartComponent" => runtime::log_component(&mut store),
-                "log" => runtime::log_component(&mut store),
-                "exceptionHandler" => runtime::exception_handler(&mut store),
-                "showSharedRWMemory" => runtime::show_memory(&mut store),
-                "printErrorMessage" => runtime::print_error_message(&mut store),
-                "writeBufferMessage" => runtime::write_buffer_message(&mut store),
-            }
+    /// Returns an error if the WebAssembly module cannot be loaded or instantiated.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let module = C::compile(path.as_ref())?;
+        let wtns = Self::instantiate(&module)?;
+        #[cfg(feature = "cache")]
+        let wtns = Self {
+            module_hash: Some(super::cache::hash_module(path)?),
+            ..wtns
         };
-        let instance = Wasm::new(Instance::new(&mut store, &module, &import_object)?);
+        Ok(wtns)
+    }
 
-        let version = instance.get_version(&mut store).unwrap_or(1);
+    /// Constructs a [`WitnessCalculator`] by instantiating a WebAssembly module compiled by the
+    /// engine `C` in a new store.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the WebAssembly instance cannot be created, or if it does not export the
+    /// functions needed to calculate witnesses.
+    pub fn instantiate(module: &C::Module) -> Result<Self> {
+        let (instance, store) = C::instantiate(module)?;
+        Self::from_instance(instance, store)
+    }
 
-        if version != 2 {
-            return Err(WitnessVersionNotSupported(version.to_string()).into());
+    /// Constructs a [`WitnessCalculator`] from a WebAssembly instance and its store.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the instance does not export the functions needed to calculate
+    /// witnesses, or if it reports a circom version or a field the engine does not support.
+    pub(super) fn from_instance(mut instance: C, mut store: C::Store) -> Result<Self> {
+        let version = instance.get_version(&mut store)?;
+        if version == 1 || version == 2 {
+            let missing = instance.missing_exports(version);
+            if !missing.is_empty() {
+                return Err(MissingExports(missing));
+            }
         }
 
-        let n32 = instance.get_field_num_len32(&mut store)?;
-        assert_eq!(n32, 8);
-        let mut safe_memory = SafeMemory::new(memory, n32 as usize, U256::ZERO);
-        instance.get_raw_prime(&mut store)?;
-        let mut arr = [0; 8];
-        for i in 0..n32 {
-            let res = instance.read_shared_rw_memory(&mut store, i)?;
-            arr[i as usize] = res;
+        let n32 = match version {
+            // Circom 1 field elements carry an 8 bytes header before their limbs.
+            1 => (instance.get_fr_len(&mut store)? >> 2).saturating_sub(2),
+            2 => instance.get_field_num_len32(&mut store)?,
+            _ => return Err(VersionNotSupported(version)),
+        };
+        if n32 == 0 {
+            return Err(UnsupportedFieldSize(n32));
         }
-        let prime = limbs_as_u256(arr);
-
-        let n64 = n32 / 2;
-        safe_memory.prime = prime;
+        let prime = instance.prepare(&mut store, version, n32)?;
 
         Ok(WitnessCalculator {
             instance,
             store,
-            memory: safe_memory,
-            n64,
+            n64: (n32 + 1) / 2,
             circom_version: version,
+            n32,
+            prime,
+            symbols: None,
+            #[cfg(feature = "cache")]
+            module_hash: None,
         })
     }
 
-    /// Calculates the witness for a given set of Circom inputs, specific to Circom version 2.
+    /// Restores the instance to its state right after instantiation, and discards the messages
+    /// buffered by the previous calculation.
+    ///
+    /// This is done automatically after a failed calculation, so that it does not affect later ones.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the state of the instance cannot be restored.
+    pub fn reset(&mut self) -> Result<()> {
+        self.instance.messages(&mut self.store).reset();
+        self.instance.reset(&mut self.store)
+    }
+
+    /// Sets where the messages printed by the circuit through circom's `log()` are sent.
+    pub fn set_log_sink(&mut self, sink: LogSink) {
+        self.instance.messages(&mut self.store).sink = sink;
+    }
+
+    /// Takes the messages collected so far by a [`LogSink::Collect`] sink.
+    pub fn take_logs(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.instance.messages(&mut self.store).logs)
+    }
+
+    /// Sets the symbol table of the circuit, used to name the missing inputs when checking inputs.
+    pub fn set_symbols(&mut self, symbols: SymbolTable) {
+        self.symbols = Some(symbols);
+    }
+
+    /// Checks that the prime the circuit was compiled for is the modulus of the field `F`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`NonMatchingPrime`](WitnessCalculatorError::NonMatchingPrime) error if the circuit prime and the modulus of `F` differ.
+    pub fn check_prime<F: PrimeField>(&self) -> Result<()> {
+        check_prime::<F>(&self.prime)
+    }
+
+    /// Checks the inputs against the input signals of the circuit, before setting any of them.
+    ///
+    /// Only circuits compiled with circom 2 report their input signals, inputs of other circuits are
+    /// not checked.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`UnknownInput`](WitnessCalculatorError::UnknownInput) error if an input is not an input signal of the circuit, an
+    /// [`InputLengthMismatch`](WitnessCalculatorError::InputLengthMismatch) error if it does not hold as many values as its signal, and a
+    /// [`MissingInputs`](WitnessCalculatorError::MissingInputs) error if fewer values are given than the circuit expects. The missing inputs
+    /// are named if the symbols of the circuit were set with [`WitnessCalculator::set_symbols`].
+    pub fn check_inputs<F: PrimeField>(&mut self, inputs: &[CircomInput<F>]) -> Result<()> {
+        if self.circom_version != 2 {
+            return Ok(());
+        }
+
+        self.instance
+            .check_inputs(&mut self.store, inputs, self.symbols.as_ref())
+    }
+
+    /// Calculates the witness for a given set of Circom inputs.
     ///
     /// # Arguments
     ///
@@ -158,164 +306,354 @@ impl WitnessCalculator {
     ///
     /// # Errors
     ///
-    /// Returns an error if the witness calculation fails.
+    /// Returns an error if the witness calculation fails, if the circuit was compiled for a prime
+    /// other than the modulus of `F`, or if the inputs do not match the input signals of the
+    /// circuit, see [`WitnessCalculator::check_inputs`].
     pub fn calculate_witness<F: PrimeFieldBits>(
         &mut self,
         inputs: Vec<CircomInput<F>>,
         sanity_check: bool,
     ) -> Result<Vec<F>> {
-        self.instance.init(&mut self.store, sanity_check)?;
-
-        if self.circom_version != 2 {
-            return Err(WitnessVersionNotSupported(self.circom_version.to_string()).into());
+        self.check_prime::<F>()?;
+        self.check_inputs(&inputs)?;
+
+        self.instance.messages(&mut self.store).reset();
+        let witness = self
+            .instance
+            .init(&mut self.store, sanity_check)
+            .and_then(|_| match self.circom_version {
+                1 => self
+                    .instance
+                    .calculate_witness_circom1(&mut self.store, inputs),
+                2 => self.calculate_witness_circom2(inputs),
+                version => Err(VersionNotSupported(version)),
+            });
+
+        // A failed calculation may leave the instance in an inconsistent state. The error of the
+        // calculation is the one worth reporting, so a failure to reset is only logged.
+        if let Err(err) = &witness {
+            if let Err(reset_err) = self.reset() {
+                log!(
+                    Level::Error,
+                    "failed to reset the witness calculator after \"{err}\": {reset_err}"
+                );
+            }
         }
 
-        let n32 = self.instance.get_field_num_len32(&mut self.store)?;
-        assert_eq!(n32, 8);
+        witness
+    }
+
+    /// Calculates the witnesses for many sets of Circom inputs, reusing this instance.
+    ///
+    /// The instance is initialized again before each set of inputs. Witnesses are calculated lazily,
+    /// as the returned iterator is advanced, and a failure only affects the witness of its own set of
+    /// inputs.
+    ///
+    /// # Arguments
+    ///
+    /// * `inputs` - The sets of Circom inputs for the computations.
+    /// * `sanity_check` - A flag to enable sanity checks during computation.
+    pub fn calculate_witnesses<'a, F, I>(
+        &'a mut self,
+        inputs: I,
+        sanity_check: bool,
+    ) -> impl Iterator<Item = Result<Vec<F>>> + 'a
+    where
+        F: PrimeFieldBits,
+        I: IntoIterator<Item = Vec<CircomInput<F>>>,
+        I::IntoIter: 'a,
+    {
+        inputs
+            .into_iter()
+            .map(move |inputs| self.calculate_witness(inputs, sanity_check))
+    }
+
+    /// Calculates the witness for a given set of Circom inputs, specific to Circom version 2.
+    ///
+    /// Inputs are written to and the witness is read from the shared read-write memory of the instance,
+    /// one field element at a time.
+    fn calculate_witness_circom2<F: PrimeFieldBits>(
+        &mut self,
+        inputs: Vec<CircomInput<F>>,
+    ) -> Result<Vec<F>> {
+        let n32 = self.n32;
 
         // allocate the inputs
         for input in inputs {
             let (msb, lsb) = fnv(&input.name);
 
             for (i, value) in input.value.into_iter().enumerate() {
-                let f_arr = ff_as_limbs(value);
-                for j in 0..n32 {
-                    self.instance
-                        .write_shared_rw_memory(&mut self.store, j, f_arr[j as usize])?;
-                }
+                self.instance.write_shared_rw_memory_limbs(
+                    &mut self.store,
+                    &ff_as_limbs(value, n32 as usize),
+                )?;
                 self.instance
                     .set_input_signal(&mut self.store, msb, lsb, i as u32)?;
             }
         }
 
-        let mut w = Vec::new();
-
         let witness_size = self.instance.get_witness_size(&mut self.store)?;
+        let mut w = Vec::with_capacity(witness_size as usize);
         for i in 0..witness_size {
             self.instance.get_witness(&mut self.store, i)?;
-            let mut arr = [0; 8];
-            for j in 0..n32 {
-                arr[j as usize] = self.instance.read_shared_rw_memory(&mut self.store, j)?;
-            }
-            w.push(limbs_as_ff(arr));
+            w.push(limbs_as_ff(
+                &self
+                    .instance
+                    .read_shared_rw_memory_limbs(&mut self.store, n32)?,
+            ));
         }
 
         Ok(w)
     }
+}
 
-    /// Retrieves the witness buffer as a byte vector.
-    ///
-    /// # Arguments
-    ///
-    /// * `store` - A mutable reference to the WebAssembly store used in computation.
-    ///
-    /// # Errors
-    ///
-    /// Returns an error if the witness buffer cannot be retrieved.
-    ///
-    /// # Returns
-    ///
-    /// A `Vec<u8>` representing the witness buffer if successful.
-    pub fn get_witness_buffer(&self, store: &mut impl AsStoreMut) -> Result<Vec<u8>> {
-        let ptr = self.instance.get_ptr_witness_buffer(store)? as usize;
-        let len = self.instance.get_n_vars(store)? * self.n64 * 8;
-        let view = self.memory.view(store);
-        let bytes = unsafe { view.data_unchecked() };
-
-        let arr = bytes[ptr..ptr + len as usize].to_vec();
-
-        Ok(arr)
+#[cfg(test)]
+pub(crate) mod tests {
+    use ff::Field;
+    use pasta_curves::{pallas, vesta};
+
+    use super::*;
+    use crate::reader::load_sym;
+
+    /// The 64-bit Goldilocks field, used by circom's `--prime goldilocks`.
+    #[derive(PrimeField)]
+    #[PrimeFieldModulus = "18446744069414584321"]
+    #[PrimeFieldGenerator = "7"]
+    #[PrimeFieldReprEndianness = "little"]
+    pub(crate) struct Goldilocks([u64; 2]);
+
+    pub(crate) use bn254::Bn254;
+
+    /// In a module of its own, as the constants derived for a field would clash with those of
+    /// [`Goldilocks`].
+    mod bn254 {
+        use ff::PrimeField;
+
+        /// The scalar field of BN254, the default prime of circom.
+        #[derive(PrimeField)]
+        #[PrimeFieldModulus = "21888242871839275222246405745257275088548364400416034343698204186575808495617"]
+        #[PrimeFieldGenerator = "7"]
+        #[PrimeFieldReprEndianness = "little"]
+        pub(crate) struct Bn254([u64; 4]);
     }
-}
 
-mod runtime {
-    //! Module `runtime` provides callback hooks for debugging and interacting with the Circom execution environment in
-    //! WebAssembly.
-    //!
-    //! These functions are typically registered as imports into the WebAssembly instance and called by the
-    //! Circom-generated WebAssembly code.
-    use super::{AsStoreMut, ExitCode, Function, Result, RuntimeError};
-    use log::error;
+    fn sha256_input<F: PrimeField>() -> Vec<CircomInput<F>> {
+        vec![CircomInput::new("arg_in".into(), vec![F::ZERO, F::ZERO])]
+    }
 
-    /// Creates a function to handle runtime errors occurring within the WebAssembly instance.
-    ///
-    /// This function is invoked when the Circom-generated code encounters a runtime error.
-    /// It logs the error details and terminates the execution with a custom [`ExitCode`].
-    ///
-    /// # Arguments
-    ///
-    /// * `store` - A mutable reference to the WebAssembly store.
-    ///
-    /// # Returns
-    ///
-    /// A [`Function`] that can be called from within the WebAssembly instance.
-    pub fn error(store: &mut impl AsStoreMut) -> Function {
-        #[allow(unused)]
-        #[allow(clippy::many_single_char_names)]
-        fn func(a: i32, b: i32, c: i32, d: i32, e: i32, f: i32) -> Result<(), RuntimeError> {
-            // NOTE: We can also get more information why it is failing, see p2str etc here:
-            // https://github.com/iden3/circom_runtime/blob/master/js/witness_calculator.js#L52-L64
-            error!("runtime error, exiting early: {a} {b} {c} {d} {e} {f}",);
-            Err(RuntimeError::user(Box::new(ExitCode(1))))
-        }
-        Function::new_typed(store, func)
+    #[test]
+    fn test_calculate_witness() {
+        let mut wtns = WitnessCalculator::new("circom/sha256/circom_sha256.wasm").unwrap();
+        let witness = wtns
+            .calculate_witness::<vesta::Base>(sha256_input(), true)
+            .unwrap();
+
+        assert_eq!(witness.len(), 29823);
+        assert_eq!(witness[0], vesta::Base::ONE);
+        assert_eq!(
+            format!("{:?}", witness[1]),
+            "0x00000000008619b3767c057fdf8e6d99fde2680c5d8517eb06761c0878d40c40"
+        );
     }
 
-    // Function definitions for Circom 2.0
+    #[test]
+    fn test_non_matching_prime() {
+        let mut wtns = WitnessCalculator::new("circom/sha256/circom_sha256.wasm").unwrap();
+        assert!(wtns.check_prime::<vesta::Base>().is_ok());
+
+        let err = wtns
+            .calculate_witness::<pallas::Base>(sha256_input(), true)
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            WitnessCalculatorError::NonMatchingPrime { .. }
+        ));
+    }
 
-    /// Handles exceptions thrown within the WebAssembly instance for Circom 2.0.
-    ///
-    /// This function is a stub and currently does nothing.
-    pub fn exception_handler(store: &mut impl AsStoreMut) -> Function {
-        #[allow(unused)]
-        fn func(a: i32) {}
-        Function::new_typed(store, func)
+    #[test]
+    fn test_calculate_witness_bn254() {
+        // A real circom 2 build, see circom/multiplier_v2/README.md.
+        let mut wtns = WitnessCalculator::new("circom/multiplier_v2/multiplier.wasm").unwrap();
+        assert_eq!(wtns.n64, 4);
+        assert!(wtns.check_prime::<Bn254>().is_ok());
+        assert!(matches!(
+            wtns.check_prime::<vesta::Base>(),
+            Err(WitnessCalculatorError::NonMatchingPrime { .. })
+        ));
+        assert!(matches!(
+            wtns.check_prime::<Goldilocks>(),
+            Err(WitnessCalculatorError::NonMatchingPrime { .. })
+        ));
+
+        let a = -Bn254::from(3);
+        let b = Bn254::from(0xffff_ffff);
+        let inputs = vec![
+            CircomInput::new("a".into(), vec![a]),
+            CircomInput::new("b".into(), vec![b]),
+        ];
+        let witness = wtns.calculate_witness(inputs, true).unwrap();
+        assert_eq!(witness, vec![Bn254::ONE, a * b, a, b]);
     }
 
-    /// Debugging function to display the shared read-write memory in Circom 2.0.
-    ///
-    /// This function is a stub and currently does nothing.
-    pub fn show_memory(store: &mut impl AsStoreMut) -> Function {
-        #[allow(unused)]
-        fn func() {}
-        Function::new_typed(store, func)
+    /// No `--prime goldilocks` build of the circom compiler is available to this test, which runs a
+    /// hand-written stand-in instead, see the header of circom/mock/adder_goldilocks.wat.
+    #[test]
+    fn test_calculate_witness_goldilocks() {
+        let mut wtns = WitnessCalculator::new("circom/mock/adder_goldilocks.wat").unwrap();
+        assert_eq!(wtns.n64, 1);
+
+        let a = -Goldilocks::ONE;
+        let b = Goldilocks::from(5);
+        let inputs = vec![
+            CircomInput::new("a".into(), vec![a]),
+            CircomInput::new("b".into(), vec![b]),
+        ];
+        let witness = wtns.calculate_witness(inputs, true).unwrap();
+        assert_eq!(witness, vec![Goldilocks::ONE, a + b, a, b]);
+
+        let err = wtns
+            .calculate_witness::<vesta::Base>(vec![], true)
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            WitnessCalculatorError::NonMatchingPrime { .. }
+        ));
     }
 
-    /// Logs error messages for Circom 2.0.
-    ///
-    /// This function is a stub and currently does nothing.
-    pub fn print_error_message(store: &mut impl AsStoreMut) -> Function {
-        #[allow(unused)]
-        fn func() {}
-        Function::new_typed(store, func)
+    #[test]
+    fn test_exceptions() {
+        let mut wtns = WitnessCalculator::new("circom/sha256/circom_sha256.wasm").unwrap();
+
+        let inputs = vec![CircomInput::new("foo".into(), vec![vesta::Base::ZERO])];
+        let err = wtns.calculate_witness(inputs, true).unwrap_err();
+        assert!(matches!(
+            err,
+            WitnessCalculatorError::UnknownInput { name } if name == "foo"
+        ));
+
+        let inputs = vec![CircomInput::new(
+            "arg_in".into(),
+            vec![vesta::Base::ZERO; 3],
+        )];
+        let err = wtns.calculate_witness(inputs, true).unwrap_err();
+        assert!(matches!(
+            err,
+            WitnessCalculatorError::InputLengthMismatch {
+                name,
+                expected: 2,
+                actual: 3,
+            } if name == "arg_in"
+        ));
+
+        let mut wtns = WitnessCalculator::new("circom/mock/adder_goldilocks.wat").unwrap();
+        let inputs = vec![
+            CircomInput::new("a".into(), vec![Goldilocks::ONE]),
+            CircomInput::new("a".into(), vec![Goldilocks::ONE]),
+        ];
+        let err = wtns.calculate_witness(inputs, true).unwrap_err();
+        assert!(matches!(err, WitnessCalculatorError::SignalAlreadySet));
     }
 
-    /// Writes buffer messages for Circom 2.0.
-    ///
-    /// This function is a stub and currently does nothing.
-    pub fn write_buffer_message(store: &mut impl AsStoreMut) -> Function {
-        #[allow(unused)]
-        fn func() {}
-        Function::new_typed(store, func)
+    #[test]
+    fn test_missing_inputs() {
+        let mut wtns = WitnessCalculator::new("circom/mock/adder_goldilocks.wat").unwrap();
+        let inputs = vec![CircomInput::new("a".into(), vec![Goldilocks::ONE])];
+
+        let err = wtns.calculate_witness(inputs.clone(), true).unwrap_err();
+        assert!(matches!(
+            err,
+            WitnessCalculatorError::MissingInputs {
+                expected: 2,
+                actual: 1,
+                missing,
+            } if missing.is_empty()
+        ));
+
+        wtns.set_symbols(load_sym("circom/mock/adder_goldilocks.sym").unwrap());
+        let err = wtns.calculate_witness(inputs, true).unwrap_err();
+        assert!(matches!(
+            err,
+            WitnessCalculatorError::MissingInputs { missing, .. } if missing == ["b"]
+        ));
     }
 
-    // Common utility functions for Circom 1 and Circom 2.0
+    #[test]
+    fn test_assert_failed() {
+        let mut wtns = WitnessCalculator::new("circom/mock/adder_goldilocks.wat").unwrap();
+        wtns.set_log_sink(LogSink::Collect);
+
+        let inputs = vec![
+            CircomInput::new("a".into(), vec![Goldilocks::ONE]),
+            CircomInput::new("b".into(), vec![Goldilocks::ONE]),
+        ];
+        let err = wtns.calculate_witness(inputs, true).unwrap_err();
+        match err {
+            WitnessCalculatorError::AssertFailed {
+                template,
+                line,
+                message,
+                error_buffer,
+            } => {
+                assert_eq!(template.as_deref(), Some("Adder_0"));
+                assert_eq!(line, Some(5));
+                assert_eq!(
+                    message.as_deref(),
+                    Some("Error in template Adder_0 line: 5")
+                );
+                assert_eq!(error_buffer, ["Error in template Adder_0 line: 5"]);
+            }
+            err => panic!("unexpected error {err:?}"),
+        }
 
-    /// Logs signals during Circom computation.
-    ///
-    /// This function is a stub and currently does nothing.
-    pub fn log_signal(store: &mut impl AsStoreMut) -> Function {
-        #[allow(unused)]
-        fn func(a: i32, b: i32) {}
-        Function::new_typed(store, func)
+        // The error buffer does not leak into the next calculation.
+        let inputs = vec![
+            CircomInput::new("a".into(), vec![Goldilocks::ONE]),
+            CircomInput::new("b".into(), vec![Goldilocks::from(2)]),
+        ];
+        let witness = wtns.calculate_witness(inputs, true).unwrap();
+        assert_eq!(witness[1], Goldilocks::from(3));
     }
 
-    /// Logs component-related messages during Circom computation.
-    ///
-    /// This function is a stub and currently does nothing.
-    pub fn log_component(store: &mut impl AsStoreMut) -> Function {
-        #[allow(unused)]
-        fn func(a: i32) {}
-        Function::new_typed(store, func)
+    #[test]
+    fn test_calculate_witnesses() {
+        let mut wtns = WitnessCalculator::new("circom/mock/adder_goldilocks.wat").unwrap();
+        let inputs = [(1, 2), (3, 3), (4, 5)].map(|(a, b)| {
+            vec![
+                CircomInput::new("a".into(), vec![Goldilocks::from(a)]),
+                CircomInput::new("b".into(), vec![Goldilocks::from(b)]),
+            ]
+        });
+
+        let witnesses = wtns.calculate_witnesses(inputs, true).collect::<Vec<_>>();
+        assert_eq!(witnesses.len(), 3);
+        assert_eq!(witnesses[0].as_ref().unwrap()[1], Goldilocks::from(3));
+        assert!(matches!(
+            witnesses[1].as_ref().unwrap_err(),
+            WitnessCalculatorError::AssertFailed { .. }
+        ));
+        assert_eq!(witnesses[2].as_ref().unwrap()[1], Goldilocks::from(9));
+    }
+
+    #[test]
+    fn test_log_sink() {
+        let mut wtns = WitnessCalculator::new("circom/mock/adder_goldilocks.wat").unwrap();
+        let inputs = vec![
+            CircomInput::new("a".into(), vec![-Goldilocks::ONE]),
+            CircomInput::new("b".into(), vec![Goldilocks::from(5)]),
+        ];
+
+        wtns.set_log_sink(LogSink::Collect);
+        wtns.calculate_witness(inputs.clone(), true).unwrap();
+        assert_eq!(wtns.take_logs(), vec!["a + b = 4"]);
+        assert!(wtns.take_logs().is_empty());
+
+        let logs = Arc::new(std::sync::Mutex::new(vec![]));
+        let sink_logs = logs.clone();
+        wtns.set_log_sink(LogSink::Callback(Arc::new(move |message| {
+            sink_logs.lock().unwrap().push(message.to_string())
+        })));
+        wtns.calculate_witness(inputs, true).unwrap();
+        assert_eq!(*logs.lock().unwrap(), vec!["a + b = 4"]);
+        assert!(wtns.take_logs().is_empty());
     }
 }